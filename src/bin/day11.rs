@@ -1,4 +1,5 @@
 // use std::fs;
+use aoc2022::cycle::CycleDetector;
 use std::collections::VecDeque;
 
 struct Game {
@@ -98,6 +99,16 @@ impl Game {
             self.turn(i);
         }
     }
+
+    /// The worry levels as far as the monkeys can tell, i.e. modulo the lcm.
+    /// Only meaningful when levels are not divided after each inspection.
+    fn key(&self) -> Vec<VecDeque<i128>> {
+        return self
+            .items
+            .iter()
+            .map(|items| items.iter().map(|level| level % self.lcm).collect())
+            .collect();
+    }
 }
 
 fn main() {
@@ -110,11 +121,11 @@ fn main() {
     business_sorted.sort_by_key(|count| -count);
     println!("{}", business_sorted[0] * business_sorted[1]);
 
+    // if the items ever get back to where they were, the business per monkey
+    // grows by the same amount every lap and the rest can be skipped
     let mut game = Game::prod(1);
-    for _ in 0..10000 {
-        game.round();
-    }
-    let mut business_sorted = game.business.clone();
+    let mut detector = CycleDetector::new(Game::key, |game: &Game| game.business.clone());
+    let mut business_sorted = detector.run(&mut game, 10000, Game::round);
     business_sorted.sort_by_key(|count| -count);
     println!("{}", business_sorted[0] * business_sorted[1]);
 }
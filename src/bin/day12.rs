@@ -1,4 +1,8 @@
-use std::collections::{BinaryHeap, HashMap};
+//! Day 12: Hill Climbing Algorithm
+//! `cargo run --bin day12 -- --heatmap > heatmap.csv` prints the distance from
+//! every point to the end instead of the answers.
+use std::collections::{HashMap, VecDeque};
+use std::env;
 use std::fs;
 
 #[derive(Ord, Eq, PartialEq, PartialOrd, Hash, Clone)]
@@ -35,9 +39,27 @@ fn parse_inputs(inputs: &str) -> (HashMap<Point, i32>, Point, Point) {
 
 /// Return a list of coordinates that the input coordinate can travel to
 fn get_neighbors(grid: &HashMap<Point, i32>, point: Point) -> Vec<Point> {
+    let height = *grid.get(&point).unwrap();
+    return adjacent(grid, point)
+        .into_iter()
+        .filter(|neighbor| *grid.get(neighbor).unwrap() <= height + 1)
+        .collect();
+}
+
+/// Return a list of coordinates that can travel to the input coordinate,
+/// i.e. get_neighbors with the climbing rule turned around
+fn get_neighbors_rev(grid: &HashMap<Point, i32>, point: Point) -> Vec<Point> {
+    let height = *grid.get(&point).unwrap();
+    return adjacent(grid, point)
+        .into_iter()
+        .filter(|neighbor| height <= *grid.get(neighbor).unwrap() + 1)
+        .collect();
+}
+
+/// The points up, down, left, and right that are on the grid
+fn adjacent(grid: &HashMap<Point, i32>, point: Point) -> Vec<Point> {
     let mut neighbors = vec![];
     let Point(r, c) = point;
-    let height = grid.get(&point).unwrap();
 
     for neighbor in [
         Point(r - 1, c),
@@ -45,58 +67,118 @@ fn get_neighbors(grid: &HashMap<Point, i32>, point: Point) -> Vec<Point> {
         Point(r, c - 1),
         Point(r, c + 1),
     ] {
-        if let Some(neighbor_height) = grid.get(&neighbor) {
-            if *neighbor_height <= *height + 1 {
-                neighbors.push(neighbor);
-            }
+        if grid.contains_key(&neighbor) {
+            neighbors.push(neighbor);
         }
     }
 
     return neighbors;
 }
 
-fn dijkstra(grid: &HashMap<Point, i32>, start: Point) -> HashMap<Point, i32> {
+/// Every step costs the same, so a breadth-first search visits points in
+/// order of distance. Return the distance to every reachable point and, for
+/// every point but the start, the point it was reached from.
+fn bfs(
+    grid: &HashMap<Point, i32>,
+    start: Point,
+    neighbors: fn(&HashMap<Point, i32>, Point) -> Vec<Point>,
+) -> (HashMap<Point, i32>, HashMap<Point, Point>) {
     let mut dists: HashMap<Point, i32> = HashMap::from([(start.clone(), 0)]);
-    let mut pq: BinaryHeap<(i32, Point)> = BinaryHeap::from([(0, start.clone())]); // this is max heap
-    let mut visited: HashMap<Point, bool> = HashMap::from([(start, true)]);
-
-    while let Some((dist, cur)) = pq.pop() {
-        if !dists.contains_key(&cur) {
-            dists.insert(cur.clone(), -dist);
-        }
+    let mut prevs: HashMap<Point, Point> = HashMap::new();
+    let mut queue: VecDeque<Point> = VecDeque::from([start]);
 
-        for neighbor in get_neighbors(&grid, cur) {
-            if !visited.contains_key(&neighbor) {
-                pq.push((dist - 1, neighbor.clone()));
-                visited.insert(neighbor, true);
+    while let Some(cur) = queue.pop_front() {
+        let dist = *dists.get(&cur).unwrap();
+        for neighbor in neighbors(grid, cur.clone()) {
+            if !dists.contains_key(&neighbor) {
+                dists.insert(neighbor.clone(), dist + 1);
+                prevs.insert(neighbor.clone(), cur.clone());
+                queue.push_back(neighbor);
             }
         }
     }
 
-    return dists;
+    return (dists, prevs);
+}
+
+/// Follow the predecessor links back from "end"; None if "end" was never
+/// reached
+fn get_path(prevs: &HashMap<Point, Point>, start: &Point, end: &Point) -> Option<Vec<Point>> {
+    let mut path = vec![end.clone()];
+    while path.last().unwrap() != start {
+        path.push(prevs.get(path.last().unwrap())?.clone());
+    }
+    path.reverse();
+    return Some(path);
+}
+
+/// Draw the path like the puzzle does: every point on the path shows the
+/// direction of the next step, the end is "E", and everything else is "."
+fn render_path(grid: &HashMap<Point, i32>, path: &[Point]) -> String {
+    let rows = grid.keys().map(|Point(r, _)| *r).max().unwrap() + 1;
+    let cols = grid.keys().map(|Point(_, c)| *c).max().unwrap() + 1;
+    let mut canvas = vec![vec!['.'; cols as usize]; rows as usize];
+
+    for step in path.windows(2) {
+        let (Point(r, c), Point(next_r, next_c)) = (&step[0], &step[1]);
+        canvas[*r as usize][*c as usize] = match (next_r - r, next_c - c) {
+            (-1, 0) => '^',
+            (1, 0) => 'v',
+            (0, -1) => '<',
+            (0, 1) => '>',
+            _ => unreachable!("steps are between neighbors"),
+        };
+    }
+    if let Some(Point(r, c)) = path.last() {
+        canvas[*r as usize][*c as usize] = 'E';
+    }
+
+    return canvas
+        .iter()
+        .map(|row| row.iter().collect::<String>() + "\n")
+        .collect();
+}
+
+/// Distance from every point to the end, one comma-separated row per grid
+/// row; points that cannot reach the end are left blank
+fn render_heatmap(grid: &HashMap<Point, i32>, dists: &HashMap<Point, i32>) -> String {
+    let rows = grid.keys().map(|Point(r, _)| *r).max().unwrap() + 1;
+    let cols = grid.keys().map(|Point(_, c)| *c).max().unwrap() + 1;
+    return (0..rows)
+        .map(|r| {
+            let cells: Vec<String> = (0..cols)
+                .map(|c| dists.get(&Point(r, c)).map_or(String::new(), |d| d.to_string()))
+                .collect();
+            return cells.join(",") + "\n";
+        })
+        .collect();
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/12.txt").unwrap();
     let (grid, start, stop) = parse_inputs(&inputs);
 
-    let dists = dijkstra(&grid, start);
+    // part 2: search backward from the end once, which gives the distance
+    // from every point to the end, then pick the closest "a"
+    let (dists_to_end, _) = bfs(&grid, stop.clone(), get_neighbors_rev);
+    if env::args().any(|arg| arg == "--heatmap") {
+        print!("{}", render_heatmap(&grid, &dists_to_end));
+        return;
+    }
+
+    let (dists, prevs) = bfs(&grid, start.clone(), get_neighbors);
     let min = dists.get(&stop).unwrap();
     println!("{min}");
-    let mut min = *min;
-
-    for point in grid.keys() {
-        let height = grid.get(point).unwrap();
-        if *height == 0 {
-            let dists = dijkstra(&grid, point.clone());
-            let local_min = dists.get(&stop);
-            match local_min {
-                Some(m) if *m < min => {
-                    min = *m;
-                }
-                _ => (),
-            }
-        }
-    }
+    let path = get_path(&prevs, &start, &stop).unwrap();
+
+    let (min, Point(r, c)) = dists_to_end
+        .iter()
+        .filter(|(point, _)| *grid.get(point).unwrap() == 0)
+        .map(|(point, dist)| (*dist, point.clone()))
+        .min()
+        .unwrap();
     println!("{min}");
+    println!("best trailhead at row {r}, column {c} (counting from 0)");
+
+    print!("{}", render_path(&grid, &path));
 }
@@ -1,4 +1,12 @@
-use std::collections::HashSet;
+//! Day 14: Regolith Reservoir
+//! Sand can be poured in three ways, see Strategy. Part 1 uses the path stack
+//! and part 2 the flood; `cargo run --bin day14 -- --check` runs every
+//! strategy that applies to each part and checks that they agree.
+//!
+//! Other setups can be poured too, with a report and a picture per source:
+//! `cargo run --bin day14 -- --sources 500,0 470,0 [--rule sand|wide|water] [--floor]`
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
@@ -7,22 +15,108 @@ struct Point {
     y: i32,
 }
 
+impl Point {
+    fn new(x: i32, y: i32) -> Self {
+        return Self { x, y };
+    }
+}
+
+/// How to pour the sand
+#[derive(Debug, Clone, Copy)]
+enum Strategy {
+    /// One grain at a time, one cell at a time
+    Step,
+    /// One grain at a time, but every grain starts where the previous one
+    /// was last able to move, since everything above it is the same
+    PathStack,
+    /// With a floor, level by level: a cell fills up if any of the cells
+    /// above it that can fall onto it does and it is not rock
+    Flood,
+}
+
+impl Strategy {
+    const ALL: [Strategy; 3] = [Strategy::Step, Strategy::PathStack, Strategy::Flood];
+
+    /// The flood needs a floor to stop at, a single source, and grains that
+    /// go one level down with every move; the others work either way
+    fn applies_to(&self, sim: &Simulation, sources: &[Point]) -> bool {
+        return match self {
+            Strategy::Flood => {
+                sim.floor.is_some() && sources.len() == 1 && sim.rule.moves.iter().all(|(_, dy)| *dy == 1)
+            }
+            _ => true,
+        };
+    }
+}
+
+/// Where a falling grain tries to go, in order of preference
+#[derive(Debug, Clone)]
+struct FallRule {
+    moves: Vec<(i32, i32)>, // (dx, dy), dy is 0 for sideways and 1 for down
+    max_slide: usize,       // sideways moves in a row before the grain settles
+}
+
+impl FallRule {
+    /// The puzzle: down, down-left, down-right
+    fn sand() -> Self {
+        return Self { moves: vec![(0, 1), (-1, 1), (1, 1)], max_slide: 0 };
+    }
+
+    /// Sand that can also slide two cells to either side on its way down
+    fn wide() -> Self {
+        return Self { moves: vec![(0, 1), (-1, 1), (1, 1), (-2, 1), (2, 1)], max_slide: 0 };
+    }
+
+    /// Falls like sand but then spreads sideways for a few cells. A grain
+    /// never turns back to the cell it just left, so it keeps going one way.
+    fn water() -> Self {
+        return Self { moves: vec![(0, 1), (-1, 1), (1, 1), (-1, 0), (1, 0)], max_slide: 4 };
+    }
+
+    fn from_name(name: &str) -> Self {
+        return match name {
+            "sand" => Self::sand(),
+            "wide" => Self::wide(),
+            "water" => Self::water(),
+            _ => panic!("Unknown rule {name}"),
+        };
+    }
+}
+
+/// How pouring from a source ended
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    Blocked, // the grains piled up to the source
+    Abyss,   // a grain fell past the deepest rock
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SourceReport {
+    source: Point,
+    grains: usize, // grains at rest from this source
+    outcome: Outcome,
+}
+
 struct Simulation {
     rocks: HashSet<Point>,
-    sands: HashSet<Point>,
+    sands: HashMap<Point, usize>, // grains and the index of their source
     bottom_line: i32,             // depth of the deepest rock
     unstable_sand: Option<Point>, // the next sand to move
+    trail: Vec<Point>,            // where the unstable sand has been, from its source
     floor: Option<i32>,
+    rule: FallRule,
 }
 
 impl Simulation {
     fn new() -> Self {
         return Self {
             rocks: HashSet::new(),
-            sands: HashSet::new(),
+            sands: HashMap::new(),
             bottom_line: 0,
             unstable_sand: None,
+            trail: vec![],
             floor: None,
+            rule: FallRule::sand(),
         };
     }
 
@@ -45,6 +139,10 @@ impl Simulation {
         self.floor = Some(self.bottom_line + 2);
     }
 
+    fn set_rule(&mut self, rule: FallRule) {
+        self.rule = rule;
+    }
+
     fn add_rock(&mut self, rock: &Point) {
         let rock = rock.clone();
         if !self.rocks.contains(&rock) {
@@ -99,38 +197,32 @@ impl Simulation {
                 return false;
             }
         }
-        return !(self.rocks.contains(p) || self.sands.contains(p));
-    }
-
-    /// part 2
-    fn is_safe_to_stand(&self, p: &Point) -> bool {
-        return !self.is_air(p) && self.is_stable();
+        return !(self.rocks.contains(p) || self.sands.contains_key(p));
     }
 
     /// the next coordinate that this sand will fall onto, unless the sand
-    /// cannot move, then return None
-    fn next_move(&self, sand: &Point) -> Option<Point> {
-        let bottom = Point {
-            x: sand.x,
-            y: sand.y + 1,
-        };
-        let diag_left = Point {
-            x: sand.x - 1,
-            y: sand.y + 1,
-        };
-        let diag_right = Point {
-            x: sand.x + 1,
-            y: sand.y + 1,
-        };
-
-        if self.is_air(&bottom) {
-            return Some(bottom);
-        }
-        if self.is_air(&diag_left) {
-            return Some(diag_left);
+    /// cannot move, then return None. "trail" is where the sand has been
+    /// before, which only matters for sideways moves.
+    fn next_move(&self, sand: &Point, trail: &[Point]) -> Option<Point> {
+        // sideways moves in a row that got the sand here
+        let mut slides = 0;
+        let mut last = sand;
+        for cell in trail.iter().rev() {
+            if cell.y != last.y {
+                break;
+            }
+            slides += 1;
+            last = cell;
         }
-        if self.is_air(&diag_right) {
-            return Some(diag_right);
+
+        for (dx, dy) in &self.rule.moves {
+            let next_ = Point::new(sand.x + dx, sand.y + dy);
+            if *dy == 0 && (slides >= self.rule.max_slide || trail.last() == Some(&next_)) {
+                continue;
+            }
+            if self.is_air(&next_) {
+                return Some(next_);
+            }
         }
 
         return None;
@@ -144,11 +236,12 @@ impl Simulation {
         return false;
     }
 
-    /// Add a sand at the set position; check if this sand is stable, if not, set
-    /// unstable_sand
-    fn add_sand(&mut self, sand: &Point) {
-        self.sands.insert(sand.clone());
-        if let Some(_) = self.next_move(sand) {
+    /// Add a sand from the source with the given index at the set position;
+    /// check if this sand is stable, if not, set unstable_sand
+    fn add_sand(&mut self, sand: &Point, source: usize) {
+        self.sands.insert(sand.clone(), source);
+        self.trail.clear();
+        if let Some(_) = self.next_move(sand, &self.trail) {
             self.unstable_sand = Some(sand.clone());
         } else {
             self.unstable_sand = None;
@@ -158,10 +251,11 @@ impl Simulation {
     /// Look through self.sands and move them. return whether sand moved or not
     fn step(&mut self) {
         if let Some(unstable_sand) = &self.unstable_sand {
-            let next_sand = self.next_move(unstable_sand).unwrap();
-            self.sands.remove(unstable_sand);
-            self.sands.insert(next_sand.clone());
-            if let Some(_) = self.next_move(&next_sand) {
+            let next_sand = self.next_move(unstable_sand, &self.trail).unwrap();
+            let source = self.sands.remove(unstable_sand).unwrap();
+            self.sands.insert(next_sand.clone(), source);
+            self.trail.push(unstable_sand.clone());
+            if let Some(_) = self.next_move(&next_sand, &self.trail) {
                 self.unstable_sand = Some(next_sand);
             } else {
                 self.unstable_sand = None;
@@ -169,8 +263,12 @@ impl Simulation {
         }
     }
 
-    /// Falls into abyss if any sand is deeper than the deepest of all rocks
+    /// Falls into abyss if any sand is deeper than the deepest of all rocks,
+    /// which cannot happen once there is a floor
     fn is_abyss(&self) -> bool {
+        if self.floor.is_some() {
+            return false;
+        }
         if let Some(unstable_sand) = &self.unstable_sand {
             return unstable_sand.y >= self.bottom_line;
         } else {
@@ -178,8 +276,131 @@ impl Simulation {
         }
     }
 
-    fn render(&self, width: i32, depth: i32, source: &Point) {
-        print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
+    /// Pour sand from the source until it either falls into the abyss or
+    /// piles up to the source. Return the number of grains at rest, which
+    /// are all in self.sands afterwards.
+    fn fill(&mut self, source: &Point, strategy: Strategy) -> usize {
+        return self.fill_from(std::slice::from_ref(source), strategy)[0].grains;
+    }
+
+    /// Pour from every source in turn, one grain each, and stop pouring from
+    /// a source once it is blocked or loses a grain to the abyss
+    fn fill_from(&mut self, sources: &[Point], strategy: Strategy) -> Vec<SourceReport> {
+        let mut outcomes: Vec<Option<Outcome>> = vec![None; sources.len()];
+        match strategy {
+            Strategy::Step => {
+                while outcomes.iter().any(|outcome| outcome.is_none()) {
+                    for (i, source) in sources.iter().enumerate() {
+                        if outcomes[i].is_none() {
+                            outcomes[i] = self.drop_by_step(source, i);
+                        }
+                    }
+                }
+            }
+            Strategy::PathStack => {
+                let mut paths: Vec<Vec<Point>> = vec![vec![]; sources.len()];
+                while outcomes.iter().any(|outcome| outcome.is_none()) {
+                    for (i, source) in sources.iter().enumerate() {
+                        if outcomes[i].is_none() {
+                            outcomes[i] = self.drop_by_path(source, i, &mut paths[i]);
+                        }
+                    }
+                }
+            }
+            Strategy::Flood => {
+                self.fill_by_flood(&sources[0]);
+                outcomes[0] = Some(Outcome::Blocked);
+            }
+        }
+
+        return sources
+            .iter()
+            .zip(outcomes)
+            .enumerate()
+            .map(|(i, (source, outcome))| SourceReport {
+                source: source.clone(),
+                grains: self.sands.values().filter(|from| **from == i).count(),
+                outcome: outcome.unwrap(),
+            })
+            .collect();
+    }
+
+    /// Drop one grain and let it fall one cell at a time. None if it came to
+    /// rest, otherwise how pouring from the source ended.
+    fn drop_by_step(&mut self, source: &Point, i: usize) -> Option<Outcome> {
+        if !self.is_air(source) {
+            return Some(Outcome::Blocked);
+        }
+        self.add_sand(source, i);
+        while !self.is_stable() && !self.is_abyss() {
+            self.step();
+        }
+        if self.is_abyss() {
+            // the grain that falls through is not at rest
+            let lost = self.unstable_sand.take().unwrap();
+            self.sands.remove(&lost);
+            return Some(Outcome::Abyss);
+        }
+        return None;
+    }
+
+    /// Drop one grain from where the previous grain from the source was last
+    /// able to move, i.e. the end of its path minus the cell it came to rest
+    /// in. Grains from other sources may have landed on the path since, and
+    /// then it is only good up to there.
+    fn drop_by_path(&mut self, source: &Point, i: usize, path: &mut Vec<Point>) -> Option<Outcome> {
+        if !self.is_air(source) {
+            return Some(Outcome::Blocked);
+        }
+        if let Some(taken) = path.iter().position(|cell| !self.is_air(cell)) {
+            path.truncate(taken);
+        }
+        if path.is_empty() {
+            path.push(source.clone());
+        }
+
+        loop {
+            let (cur, trail) = path.split_last().unwrap();
+            match self.next_move(cur, trail) {
+                Some(next) if self.floor.is_none() && next.y > self.bottom_line => {
+                    return Some(Outcome::Abyss); // this grain and every one after it is lost
+                }
+                Some(next) => path.push(next),
+                None => {
+                    let sand = path.pop().unwrap();
+                    self.sands.insert(sand, i);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn fill_by_flood(&mut self, source: &Point) {
+        let floor = self.floor.expect("the flood needs a floor");
+        let mut cur_level: HashSet<Point> = HashSet::new();
+        if self.is_air(source) {
+            cur_level.insert(source.clone());
+        }
+
+        while !cur_level.is_empty() && cur_level.iter().next().unwrap().y < floor {
+            let mut next_level = HashSet::new();
+            cur_level.iter().for_each(|sand| {
+                for (dx, dy) in &self.rule.moves {
+                    let next_ = Point::new(sand.x + dx, sand.y + dy);
+                    if self.is_air(&next_) {
+                        next_level.insert(next_);
+                    }
+                }
+            });
+            self.sands.extend(cur_level.into_iter().map(|sand| (sand, 0)));
+            cur_level = next_level;
+        }
+    }
+
+    /// Draw the cave around sources[focus]: grains from it are "o", grains
+    /// from other sources are "~", and sources are "X"
+    fn render(&self, width: i32, depth: i32, sources: &[Point], focus: usize) {
+        let source = &sources[focus];
         let mut grid: Vec<String> = Vec::new();
         for d in 0..=depth {
             let mut line = String::new();
@@ -192,9 +413,13 @@ impl Simulation {
 
                 if self.rocks.contains(&p) {
                     line.push_str("#");
-                } else if self.sands.contains(&p) {
-                    line.push_str("o");
-                } else if p == *source {
+                } else if let Some(from) = self.sands.get(&p) {
+                    if *from == focus {
+                        line.push_str("o");
+                    } else {
+                        line.push_str("~");
+                    }
+                } else if sources.contains(&p) {
                     line.push_str("X");
                 } else if let Some(floor) = self.floor {
                     if p.y >= floor {
@@ -214,32 +439,97 @@ impl Simulation {
     }
 }
 
+/// Pour with every strategy that applies and check that they leave the
+/// same grains behind, from the same sources
+fn check(inputs: &str, sources: &[Point], rule: &FallRule, with_floor: bool) -> Vec<SourceReport> {
+    let results: Vec<(Strategy, Vec<SourceReport>, HashMap<Point, usize>)> = Strategy::ALL
+        .iter()
+        .filter_map(|strategy| {
+            let mut sim = Simulation::from_input(inputs);
+            sim.set_rule(rule.clone());
+            if with_floor {
+                sim.set_floor();
+            }
+            if !strategy.applies_to(&sim, sources) {
+                return None;
+            }
+            let reports = sim.fill_from(sources, *strategy);
+            return Some((*strategy, reports, sim.sands));
+        })
+        .collect();
+    let (first, reports, sands) = &results[0];
+    for (strategy, other_reports, other_sands) in &results[1..] {
+        assert!(sands == other_sands && reports == other_reports, "{first:?} and {strategy:?} disagree");
+    }
+    return reports.clone();
+}
+
+/// "x,y"
+fn parse_point(arg: &str) -> Point {
+    let (x, y) = arg.split_once(',').expect("points look like 500,0");
+    return Point::new(x.parse().unwrap(), y.parse().unwrap());
+}
+
 /// One part for parsing inputs into a simulation struct
 ///
 /// A second part for running the simulation
 fn main() {
     let inputs = fs::read_to_string("inputs/14.txt").unwrap();
-    let mut sim = Simulation::from_input(&inputs);
+    let args: Vec<String> = env::args().collect();
+    let source = Point::new(500, 0);
+    if args.iter().any(|arg| arg == "--check") {
+        // the puzzle on both inputs, then every rule and several sources on
+        // the sample only, since stepping through the real input is slow
+        for path in ["inputs/14.test", "inputs/14.txt"] {
+            let inputs = fs::read_to_string(path).unwrap();
+            let sources = std::slice::from_ref(&source);
+            let counts = (
+                check(&inputs, sources, &FallRule::sand(), false)[0].grains,
+                check(&inputs, sources, &FallRule::sand(), true)[0].grains,
+            );
+            println!("{path}: all strategies agree on {counts:?}");
+        }
+        let sample = fs::read_to_string("inputs/14.test").unwrap();
+        for rule in ["sand", "wide", "water"] {
+            let rule = FallRule::from_name(rule);
+            for sources in [vec![source.clone()], vec![source.clone(), Point::new(497, 0), Point::new(503, 2)]] {
+                check(&sample, &sources, &rule, false);
+                check(&sample, &sources, &rule, true);
+            }
+        }
+        println!("inputs/14.test: all strategies agree with every rule and several sources");
+    }
 
-    while !sim.is_abyss() {
-        sim.add_sand(&Point { x: 500, y: 0 });
-        while !sim.is_stable() && !sim.is_abyss() {
-            sim.step();
+    if let Some(i) = args.iter().position(|arg| arg == "--sources") {
+        let sources: Vec<Point> = args[i + 1..]
+            .iter()
+            .take_while(|arg| !arg.starts_with("--"))
+            .map(|arg| parse_point(arg))
+            .collect();
+        let mut sim = Simulation::from_input(&inputs);
+        if let Some(i) = args.iter().position(|arg| arg == "--rule") {
+            sim.set_rule(FallRule::from_name(&args[i + 1]));
+        }
+        if args.iter().any(|arg| arg == "--floor") {
+            sim.set_floor();
         }
+        let reports = sim.fill_from(&sources, Strategy::PathStack);
+        let depth = sim.floor.unwrap_or(sim.bottom_line);
+        for (i, report) in reports.iter().enumerate() {
+            println!(
+                "source {} at ({}, {}): {} grains, {:?}",
+                i + 1, report.source.x, report.source.y, report.grains, report.outcome
+            );
+            sim.render(40, depth, &sources, i);
+        }
+        return;
     }
-    println!("{:?}", sim.sands.len() - 1);
+
+    let mut sim = Simulation::from_input(&inputs);
+    println!("{}", sim.fill(&source, Strategy::PathStack));
 
     // part 2
     let mut sim = Simulation::from_input(&inputs);
     sim.set_floor();
-
-    let source = Point { x: 500, y: 0 };
-    while !sim.is_safe_to_stand(&source) {
-        sim.add_sand(&source);
-        while !sim.is_stable() {
-            sim.step();
-        }
-        // sim.render(60, 50, &source);
-    }
-    println!("{:?}", sim.sands.len());
+    println!("{}", sim.fill(&source, Strategy::Flood));
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use aoc2022::interval::{IntervalSet, Range};
 use std::fs;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -24,142 +24,16 @@ impl Point {
         return xdelta.abs() + ydelta.abs();
     }
 
-    /// Return the top, bottom, left, and right limits of the circle centered
+    /// Return the left, right, top, and bottom limits of the circle centered
     /// at self and with a point at p
     fn limits(&self, p: &Point) -> (i128, i128, i128, i128) {
-        let (mut left, mut right, mut top, mut bottom) = (self.x, self.x, self.y, self.y);
-
-        while self.dist(&Point { x: left, y: self.y }) <= self.dist(p) {
-            left -= 1;
-        }
-
-        while self.dist(&Point {
-            x: right,
-            y: self.y,
-        }) <= self.dist(p)
-        {
-            right += 1;
-        }
-        while self.dist(&Point { x: self.x, y: top }) <= self.dist(p) {
-            top -= 1;
-        }
-        while self.dist(&Point {
-            x: self.x,
-            y: bottom,
-        }) <= self.dist(p)
-        {
-            bottom += 1;
-        }
-        return (left, right, top, bottom);
-    }
-}
-
-/// Inclusive on both ends
-#[derive(PartialEq, Eq, Clone, Debug, Hash)]
-struct Range {
-    start: i128,
-    stop: i128,
-}
-
-impl Range {
-    fn len(&self) -> i128 {
-        return self.stop - self.start + 1;
-    }
-
-    fn new(start: i128, stop: i128) -> Self {
-        return Self { start, stop };
-    }
-
-    fn intersect(&self, other: &Range) -> Option<Self> {
-        if self.stop < other.start || self.start > other.stop {
-            return None;
-        }
-        let start = self.start.max(other.start);
-        let stop = self.stop.min(other.stop);
-        return Some(Self::new(start, stop));
-    }
-
-    fn can_union(&self, other: &Range) -> bool {
-        return !(self.stop < other.start - 1) && !(self.start > other.stop + 1);
-    }
-
-    /// Assume that the two actually overlaps
-    fn union(&self, other: &Range) -> Self {
-        let start = self.start.min(other.start);
-        let stop = self.stop.max(other.stop);
-        return Self::new(start, stop);
-    }
-}
-
-/// A collection of ranges
-#[derive(Debug)]
-struct Coverage {
-    ranges: HashSet<Range>, // disjoint!
-}
-
-impl Coverage {
-    fn get_gap(&self) -> i128 {
-        let mut ranges = vec![];
-        self.ranges
-            .iter()
-            .for_each(|range| ranges.push(range.clone()));
-        ranges.sort_by_key(|range| range.start);
-
-        let mut gap = 0;
-        ranges.windows(2).for_each(|two_ranges| {
-            let first_r = &two_ranges[0];
-            let second_r = &two_ranges[1];
-            if first_r.stop + 2 == second_r.start {
-                gap = first_r.stop + 1;
-            }
-        });
-
-        return gap;
-    }
-
-    fn new() -> Self {
-        return Self {
-            ranges: HashSet::new(),
-        };
-    }
-
-    /// Number of numbers that fall within any of the ranges
-    fn len(&self) -> i128 {
-        return self
-            .ranges
-            .iter()
-            .map(|range| range.stop - range.start + 1)
-            .sum::<i128>();
-    }
-
-    fn add(&mut self, new: Range) {
-        let mut intersected: Option<Range> = None;
-
-        for range in &self.ranges {
-            if range.can_union(&new) {
-                intersected = Some(range.clone());
-            }
-        }
-
-        if let Some(range) = intersected {
-            self.ranges.remove(&range);
-            let new = new.union(&range);
-            self.add(new);
-        } else {
-            self.ranges.insert(new);
-        }
-    }
-
-    /// Intersect each element with other. If None, remove the element. If Some, insert back in
-    fn intersect(&mut self, other: &Range) {
-        let mut ranges = vec![];
-        self.ranges.drain().for_each(|range| ranges.push(range));
-        ranges.iter().for_each(|range| {
-            let intersection = range.intersect(other);
-            if let Some(intersection) = intersection {
-                self.ranges.insert(intersection);
-            }
-        });
+        let radius = self.dist(p);
+        return (
+            self.x - radius,
+            self.x + radius,
+            self.y - radius,
+            self.y + radius,
+        );
     }
 }
 
@@ -188,7 +62,7 @@ impl Network {
     /// return the left most and right most x's such that (x, y)
     /// cannot be a valid beacon, unless the line does not intersect with the
     /// diamond, then return None
-    fn coverage(sensor: &Point, beacon: &Point, y: i128) -> Option<Range> {
+    fn coverage(sensor: &Point, beacon: &Point, y: i128) -> Option<Range<i128>> {
         let dist = sensor.dist(beacon);
         let ydelta = (y - sensor.y).abs();
 
@@ -222,6 +96,34 @@ impl Network {
         // println!("{left} {right} {top} {bottom}");
     }
 
+    /// The cells on row y that are covered by at least one sensor. The ranges
+    /// are sorted by their start first so that each insert lands at the end
+    /// of the set.
+    fn row_coverage(&self, y: i128) -> IntervalSet<i128> {
+        let mut ranges: Vec<Range<i128>> = self
+            .readings
+            .iter()
+            .filter_map(|(sensor, beacon)| Network::coverage(sensor, beacon, y))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        return IntervalSet::from_ranges(ranges);
+    }
+
+    /// Number of cells on row y where a beacon cannot be: every covered cell
+    /// except the ones that already hold a known beacon
+    fn count_excluded(&self, y: i128) -> i128 {
+        let coverage = self.row_coverage(y);
+        let mut beacons: Vec<i128> = self
+            .readings
+            .iter()
+            .filter(|(_, beacon)| beacon.y == y && coverage.contains(beacon.x))
+            .map(|(_, beacon)| beacon.x)
+            .collect();
+        beacons.sort();
+        beacons.dedup();
+        return coverage.len() - beacons.len() as i128;
+    }
+
     /// Not at or closer to any sensor than that sensor's closest beacon
     fn is_valid_beacon(&self, p: &Point, count_beacon: bool) -> bool {
         let mut is_beacon = false;
@@ -243,15 +145,134 @@ impl Network {
         // println!("{p:?} {is_beacon} {is_outside}");
         return is_beacon && count_beacon || is_outside;
     }
+
+    /// The lines just outside the diamond of each reading, i.e. the points at
+    /// distance (dist + 1) from the sensor. Diagonals going down-right are
+    /// written as x - y = b and diagonals going up-right as x + y = a, so the
+    /// lines are returned as (a's, b's).
+    fn boundary_lines(&self) -> (Vec<i128>, Vec<i128>) {
+        let (mut sums, mut diffs) = (vec![], vec![]);
+        for (sensor, beacon) in &self.readings {
+            let reach = sensor.dist(beacon) + 1;
+            sums.push(sensor.x + sensor.y - reach);
+            sums.push(sensor.x + sensor.y + reach);
+            diffs.push(sensor.x - sensor.y - reach);
+            diffs.push(sensor.x - sensor.y + reach);
+        }
+        sums.sort();
+        sums.dedup();
+        diffs.sort();
+        diffs.dedup();
+        return (sums, diffs);
+    }
+
+    /// Whole lines squeezed between two parallel boundary lines that are two
+    /// apart, i.e. one-cell corridors between two diamonds.
+    fn corridor_lines(lines: &[i128]) -> Vec<i128> {
+        return lines
+            .iter()
+            .filter(|line| lines.binary_search(&(*line + 2)).is_ok())
+            .map(|line| line + 1)
+            .collect();
+    }
+
+    /// Find the only point in [0, limit] x [0, limit] that no sensor covers.
+    /// Every covered neighbor of such a point puts it right outside that
+    /// sensor's diamond, so the point usually sits where a boundary line
+    /// crosses another line: a boundary line, a corridor between two
+    /// boundary lines, or an edge of the search area. The corners are tried
+    /// too. Should none of these candidates be uncovered, the area is split
+    /// into quadrants instead. Return the point, or an error if every cell is
+    /// covered, and every candidate that was checked.
+    fn find_distress_beacon(&self, limit: i128) -> (Result<Point, String>, Vec<Point>) {
+        let (mut sums, mut diffs) = self.boundary_lines();
+        sums.extend(Network::corridor_lines(&sums));
+        diffs.extend(Network::corridor_lines(&diffs));
+        let mut candidates = vec![
+            Point { x: 0, y: 0 },
+            Point { x: 0, y: limit },
+            Point { x: limit, y: 0 },
+            Point { x: limit, y: limit },
+        ];
+        for a in &sums {
+            for b in &diffs {
+                // x + y = a, x - y = b only meet on the grid if a - b is even
+                if (a - b).rem_euclid(2) != 0 {
+                    continue;
+                }
+                candidates.push(Point { x: (a + b) / 2, y: (a - b) / 2 });
+            }
+        }
+        // where the lines meet x = 0, x = limit, y = 0, and y = limit
+        for a in &sums {
+            candidates.push(Point { x: 0, y: *a });
+            candidates.push(Point { x: limit, y: a - limit });
+            candidates.push(Point { x: *a, y: 0 });
+            candidates.push(Point { x: a - limit, y: limit });
+        }
+        for b in &diffs {
+            candidates.push(Point { x: 0, y: -b });
+            candidates.push(Point { x: limit, y: limit - b });
+            candidates.push(Point { x: *b, y: 0 });
+            candidates.push(Point { x: b + limit, y: limit });
+        }
+        candidates.retain(|p| 0 <= p.x && p.x <= limit && 0 <= p.y && p.y <= limit);
+
+        let found = candidates
+            .iter()
+            .find(|p| self.is_valid_beacon(p, false))
+            .cloned()
+            .or_else(|| self.search_quadrants(0, 0, limit, limit))
+            .ok_or(format!("every cell in [0, {limit}] x [0, {limit}] is covered"));
+        return (found, candidates);
+    }
+
+    /// Look for an uncovered cell in [x0, x1] x [y0, y1]. A diamond is
+    /// convex, so a box whose four corners are all within one sensor's reach
+    /// is covered; otherwise split it into four and look in each.
+    fn search_quadrants(&self, x0: i128, y0: i128, x1: i128, y1: i128) -> Option<Point> {
+        if x0 > x1 || y0 > y1 {
+            return None;
+        }
+        let corners = [
+            Point { x: x0, y: y0 },
+            Point { x: x0, y: y1 },
+            Point { x: x1, y: y0 },
+            Point { x: x1, y: y1 },
+        ];
+        let covered = self.readings.iter().any(|(sensor, beacon)| {
+            let radius = sensor.dist(beacon);
+            return corners.iter().all(|corner| sensor.dist(corner) <= radius);
+        });
+        if covered {
+            return None;
+        }
+        if x0 == x1 && y0 == y1 {
+            return Some(Point { x: x0, y: y0 });
+        }
+        let (xm, ym) = ((x0 + x1) / 2, (y0 + y1) / 2);
+        return self
+            .search_quadrants(x0, y0, xm, ym)
+            .or_else(|| self.search_quadrants(xm + 1, y0, x1, ym))
+            .or_else(|| self.search_quadrants(x0, ym + 1, xm, y1))
+            .or_else(|| self.search_quadrants(xm + 1, ym + 1, x1, y1));
+    }
+
+    /// The original part 2: build the coverage of every row and look for the
+    /// row with a gap. Correct but takes about a minute on the real input.
+    fn scan_rows(&self, limit: i128) -> Option<Point> {
+        for y in 0..=limit {
+            // the first cell of the row that no sensor covers, if any
+            if let Some(gap) = self.row_coverage(y).gaps_within(Range::new(0, limit)).first() {
+                return Some(Point { x: gap.start, y });
+            }
+        }
+        return None;
+    }
 }
 
-fn main() {
-    let inputs = fs::read_to_string("inputs/15.txt").unwrap();
-    let y = 2000000;
-    let limit = 4000000;
-    let multiplier = 4000000;
+fn parse_network(inputs: &str) -> Network {
     let mut network = Network::new();
-
     inputs.lines().for_each(|line| {
         let mut clauses = line.split(": closest beacon is at ");
         let sensor_clause = &clauses.next().unwrap()[10..];
@@ -260,34 +281,36 @@ fn main() {
         let beacon = Point::parse_coordinate_str(beacon_clause);
         network.add_sensor(&sensor, &beacon);
     });
+    return network;
+}
 
-    let mut count = 0;
-    for x in network.left..=network.right {
-        let point = Point { x, y };
-        if !network.is_valid_beacon(&point, true) {
-            count += 1;
-        }
-    }
+fn main() {
+    // the boundary search should agree with the row scan on the sample
+    let sample = parse_network(&fs::read_to_string("inputs/15.test").unwrap());
+    let (found, _) = sample.find_distress_beacon(20);
+    assert_eq!(found.ok(), sample.scan_rows(20));
+    // and so should the row intervals with checking every cell
+    let excluded = (sample.left..=sample.right)
+        .filter(|x| !sample.is_valid_beacon(&Point { x: *x, y: 10 }, true))
+        .count();
+    assert_eq!(sample.count_excluded(10), excluded as i128);
+
+    let inputs = fs::read_to_string("inputs/15.txt").unwrap();
+    let y = 2000000;
+    let limit = 4000000;
+    let multiplier = 4000000;
+    let network = parse_network(&inputs);
+
+    let count = network.count_excluded(y);
     println!("{}", count);
 
     // part 2:
-    // Iterating over all (limit * limit) possibilities is not an option
-    let mut tuning_freq = 0;
-    for y in 0..=limit {
-        let entire_row = Range::new(0, limit);
-        let mut coverage = Coverage::new();
-        for (sensor, beacon) in network.readings.iter() {
-            let range = Network::coverage(sensor, beacon, y);
-            if let Some(range) = range {
-                coverage.add(range);
-            }
-        }
-        coverage.intersect(&entire_row);
-        if coverage.len() != (limit + 1) {
-            // have found the coverage we are looking for
-            let x = coverage.get_gap();
-            tuning_freq = x * multiplier + y;
-        }
+    // Iterating over all (limit * limit) possibilities is not an option, and
+    // neither is building the coverage of all (limit + 1) rows
+    let (found, candidates) = network.find_distress_beacon(limit);
+    println!("checked {} candidates", candidates.len());
+    match found {
+        Ok(beacon) => println!("{}", beacon.x * multiplier + beacon.y),
+        Err(err) => println!("No distress beacon: {err}"),
     }
-    println!("{tuning_freq}");
 }
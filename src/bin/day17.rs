@@ -3,16 +3,87 @@
 //! units from the wall, and its bottom edge is 3 units from the the highest
 //! rock or floor. After a rock is spawned, it is first pushed by the stream
 //! then falls down.
-use std::collections::{HashMap, HashSet};
+//!
+//! The width, the spawn offsets, and the shapes can all be changed to explore
+//! variants of the puzzle:
+//! `cargo run --bin day17 <shapes file> <width> <spawn x> <spawn gap>`, where
+//! the shapes file has '#' pictures separated by blank lines like
+//! DEFAULT_SHAPES below. With `--check`, a million rocks are also dropped one
+//! by one to check the extrapolation.
+//!
+//! The chamber is stored as one bitmask per row, where bit x is set if column
+//! x is taken, and rocks are stored the same way, so pushing a rock is a bit
+//! shift and checking for collisions is a bitwise and. Rows that no rock can
+//! reach anymore are dropped as the tower grows.
+use aoc2022::cycle::CycleDetector;
+use std::collections::VecDeque;
+use std::env;
 use std::fs;
 
+const DEFAULT_SHAPES: &str = "\
+####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##
+";
+
+/// A shape as one bitmask per row, bottom row first, flush against the left
+/// wall
 #[derive(Debug)]
-enum Shape {
-    Hori,
-    Cross,
-    Corner,
-    Verti,
-    Square,
+struct Shape {
+    rows: Vec<u8>,
+}
+
+impl Shape {
+    /// A picture like "..#\n..#\n###", where the first line is the top row
+    fn from_picture(picture: &str) -> Self {
+        let rows = picture
+            .lines()
+            .rev()
+            .map(|line| {
+                line.chars().enumerate().fold(0u8, |mask, (x, c)| match c {
+                    '#' if x < 8 => mask | (1 << x),
+                    '#' => panic!("shapes are at most 8 units wide"),
+                    '.' => mask,
+                    _ => panic!("Illegal shape {picture}"),
+                })
+            })
+            .collect();
+        return Self { rows };
+    }
+
+    /// Number of columns between the left wall and the right edge
+    fn width(&self) -> i64 {
+        return self
+            .rows
+            .iter()
+            .map(|mask| 8 - mask.leading_zeros() as i64)
+            .max()
+            .unwrap_or(0);
+    }
+}
+
+/// Pictures separated by blank lines
+fn parse_shapes(inputs: &str) -> Vec<Shape> {
+    return inputs
+        .split("\n\n")
+        .map(|picture| picture.trim())
+        .filter(|picture| !picture.is_empty())
+        .map(Shape::from_picture)
+        .collect();
 }
 
 enum Dir {
@@ -21,225 +92,206 @@ enum Dir {
     Down,
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
-struct Point {
-    x: i64,
-    y: i64,
-}
-
-impl Point {
-    fn new(x: i64, y: i64) -> Self {
-        return Self { x, y };
-    }
+/// A rock as one bitmask per row, bottom row first
+#[derive(Clone, Debug)]
+struct Rock {
+    rows: Vec<u8>,
+    y: i64, // level of the bottom row
 }
 
 struct Simulation {
     peak: i64, // level of the highest rock; level starts at 0
     width: i64,
-    stable_rocks: HashSet<Point>,
-    moving_rocks: HashSet<Point>,
+    spawn_x: i64,   // gap between the left wall and a new rock
+    spawn_gap: i64, // gap between the peak and the bottom of a new rock
+    pruned: i64,       // number of rows at the bottom that have been dropped
+    rows: Vec<u8>,     // rows[i] is the bitmask of level (pruned + i)
+    round: usize,      // number of rocks that have landed
+    stream_cur: usize, // next jet to push the rock, always within the stream
 }
 
 impl Simulation {
-    fn new(width: i64) -> Self {
+    fn new(width: i64, spawn_x: i64, spawn_gap: i64) -> Self {
+        if !(1..=8).contains(&width) {
+            panic!("rows are bitmasks of a u8, so the width must be 1 to 8");
+        }
         return Self {
             peak: -1,
             width,
-            stable_rocks: HashSet::new(),
-            moving_rocks: HashSet::new(),
+            spawn_x,
+            spawn_gap,
+            pruned: 0,
+            rows: vec![],
+            round: 0,
+            stream_cur: 0,
         };
     }
 
-    /// Check that the set of rocks is not out of bounds: too left, too right,
-    /// lower than the floor
-    fn is_in_bounds(&self, rocks: &HashSet<Point>) -> bool {
-        if rocks.len() == 0 {
-            return true; // empty set is trivially within bounds
+    /// Bitmask of the level; the floor and the dropped rows count as full
+    fn row(&self, y: i64) -> u8 {
+        if y < self.pruned {
+            return u8::MAX;
         }
-        let (mut x_min, mut x_max, mut y_min) = (0, 0, 0);
-
-        rocks.iter().for_each(|rock| {
-            x_min = x_min.min(rock.x);
-            x_max = x_max.max(rock.x);
-            y_min = y_min.min(rock.y);
-        });
-
-        return (x_min >= 0) && (x_max < self.width) && (y_min >= 0);
+        return *self.rows.get((y - self.pruned) as usize).unwrap_or(&0);
     }
 
-    /// Check that the input set of rocks does not collide with existing set
-    /// of stable rocks
-    fn is_colliding(&self, rocks: &HashSet<Point>) -> bool {
-        let intersection: Vec<&Point> = rocks.intersection(&self.stable_rocks).collect();
-        return intersection.len() > 0;
+    /// Check that the rock does not go through the floor or collide with the
+    /// stable rocks
+    fn is_colliding(&self, rock: &Rock) -> bool {
+        return rock
+            .rows
+            .iter()
+            .enumerate()
+            .any(|(i, mask)| self.row(rock.y + i as i64) & mask != 0);
     }
 
-    fn get_next_move(&self, dir: Dir) -> HashSet<Point> {
-        let (x_delta, y_delta) = match dir {
-            Dir::Left => (-1, 0),
-            Dir::Right => (1, 0),
-            Dir::Down => (0, -1),
+    /// The rock after the move, or None if it would go through a wall
+    fn get_next_move(&self, rock: &Rock, dir: Dir) -> Option<Rock> {
+        let right_wall = 1u8 << (self.width - 1);
+        let rows = match dir {
+            Dir::Left if rock.rows.iter().all(|mask| mask & 1 == 0) => {
+                rock.rows.iter().map(|mask| mask >> 1).collect()
+            }
+            Dir::Right if rock.rows.iter().all(|mask| mask & right_wall == 0) => {
+                rock.rows.iter().map(|mask| mask << 1).collect()
+            }
+            Dir::Down => rock.rows.clone(),
+            _ => return None,
         };
-        let mut after_move = HashSet::new();
-        self.moving_rocks.iter().for_each(|rock| {
-            after_move.insert(Point::new(rock.x + x_delta, rock.y + y_delta));
-        });
-
-        return after_move;
-    }
-
-    /// Given that "new" is a legal next move, commit the move by replacing
-    /// self.unstable_rocks
-    fn commit_move(&mut self, new: HashSet<Point>) {
-        self.moving_rocks = new;
-    }
-
-    /// Assuming that the moving rocks has landed, transition it into the set
-    /// of stable rocks and update the peak
-    fn stabilize_moving_rocks(&mut self) {
-        self.moving_rocks.drain().for_each(|rock| {
-            self.peak = self.peak.max(rock.y);
-            self.stable_rocks.insert(rock);
-        });
-    }
-
-    /// Assuming that self.moving_rocks is empty, create a new set and replace
-    /// it
-    fn spawn(&mut self, shape: &Shape) {
-        let rocks: Vec<Point> = match shape {
-            Shape::Hori => vec![
-                Point::new(2, self.peak + 4), // self.peak can be -1
-                Point::new(3, self.peak + 4),
-                Point::new(4, self.peak + 4),
-                Point::new(5, self.peak + 4),
-            ],
-            Shape::Cross => vec![
-                Point::new(2, self.peak + 5),
-                Point::new(3, self.peak + 4),
-                Point::new(3, self.peak + 5),
-                Point::new(3, self.peak + 6),
-                Point::new(4, self.peak + 5),
-            ],
-            Shape::Corner => vec![
-                Point::new(2, self.peak + 4),
-                Point::new(3, self.peak + 4),
-                Point::new(4, self.peak + 4),
-                Point::new(4, self.peak + 5),
-                Point::new(4, self.peak + 6),
-            ],
-            Shape::Verti => vec![
-                Point::new(2, self.peak + 4),
-                Point::new(2, self.peak + 5),
-                Point::new(2, self.peak + 6),
-                Point::new(2, self.peak + 7),
-            ],
-            Shape::Square => vec![
-                Point::new(2, self.peak + 4),
-                Point::new(3, self.peak + 4),
-                Point::new(2, self.peak + 5),
-                Point::new(3, self.peak + 5),
-            ],
+        let y = match dir {
+            Dir::Down => rock.y - 1,
+            _ => rock.y,
         };
-        let mut output = HashSet::new();
-        rocks.iter().for_each(|rock| {
-            output.insert(rock.clone());
-        });
-        self.moving_rocks = output;
+        return Some(Rock { rows, y });
     }
 
-    /// Print the top N rows
-    fn stringify_top(&self, n: usize) -> String {
-        let mut s = String::new();
-
-        for row_from_top in 0..n {
-            for col in 0..self.width {
-                let p = Point::new(col, self.peak - row_from_top as i64);
-                if self.stable_rocks.contains(&p) {
-                    s.push_str("#");
-                } else {
-                    s.push_str(".");
-                }
+    /// Assuming that the rock has landed, add it to the stable rows and
+    /// update the peak
+    fn stabilize(&mut self, rock: &Rock) {
+        for (i, mask) in rock.rows.iter().enumerate() {
+            let y = rock.y + i as i64;
+            let at = (y - self.pruned) as usize;
+            if at >= self.rows.len() {
+                self.rows.resize(at + 1, 0);
+            }
+            self.rows[at] |= mask;
+            if *mask != 0 {
+                self.peak = self.peak.max(y);
             }
-            s.push_str("\n");
         }
+    }
 
-        return s;
+    /// Create the rock with its left edge spawn_x units from the wall and its
+    /// bottom edge spawn_gap units above the peak
+    fn spawn(&self, shape: &Shape) -> Rock {
+        if self.spawn_x + shape.width() > self.width {
+            panic!("{shape:?} does not fit in a chamber {} units wide", self.width);
+        }
+        return Rock {
+            rows: shape.rows.iter().map(|mask| mask << self.spawn_x).collect(),
+            y: self.peak + 1 + self.spawn_gap, // self.peak can be -1
+        };
     }
-}
 
-fn simulate(rounds: usize, stream: &[char]) {
-    let mut stream_cur: usize = 0;
-    let new_shapes = vec![
-        Shape::Hori,
-        Shape::Cross,
-        Shape::Corner,
-        Shape::Verti,
-        Shape::Square,
-    ];
-    let mut sim = Simulation::new(7);
-    // (shape, stream, snapshot) -> (round, height)
-    let mut footprints: HashMap<(usize, usize, String), (usize, i64)> = HashMap::new();
-    let mut round = 0usize;
-    let mut skipped = 0i64;
-    let multiplier = 100;
-    let snapshot_rows = 20;
-
-    while round < rounds {
-        let shape_i = round % new_shapes.len();
-        stream_cur = stream_cur % stream.len();
-        let snapshot = sim.stringify_top(snapshot_rows);
-        if skipped == 0 {
-            if let Some((prev_round, prev_height)) =
-                footprints.get(&(shape_i, stream_cur, snapshot.clone()))
-            {
-                // println!("shape {shape_i} stream {stream_cur}");
-                // println!("  prev round {prev_round} prev height {prev_height}");
-                // println!("  curr round {round} cur height {}", sim.peak);
-                let increment = sim.peak - prev_height;
-                let period = round - prev_round;
-                // println!("  fast forwarding period {period} increment {increment}");
-
-                while round + multiplier * period < rounds {
-                    // println!("  skipped to {round}");
-                    round += multiplier * period;
-                    skipped += (multiplier as i64) * increment;
+    /// Flood the air from the row above the peak and drop every row below
+    /// the lowest one that the air reaches, since no rock can get there
+    fn prune(&mut self) {
+        let top = self.peak + 1;
+        let mut lowest = top;
+        let mut visited: Vec<u8> = vec![0; (top - self.pruned + 1) as usize];
+        let mut queue = VecDeque::new();
+        for x in 0..self.width {
+            queue.push_back((x, top));
+            visited[(top - self.pruned) as usize] |= 1 << x;
+        }
+        while let Some((x, y)) = queue.pop_front() {
+            lowest = lowest.min(y);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if nx < 0 || nx >= self.width || ny < self.pruned || ny > top {
+                    continue;
+                }
+                let bit = 1u8 << nx;
+                let at = (ny - self.pruned) as usize;
+                if self.row(ny) & bit != 0 || visited[at] & bit != 0 {
+                    continue;
                 }
-            } else {
-                footprints.insert((shape_i, stream_cur, snapshot), (round, sim.peak));
+                visited[at] |= bit;
+                queue.push_back((nx, ny));
             }
         }
+        self.rows.drain(..((lowest - self.pruned) as usize).min(self.rows.len()));
+        self.pruned = lowest;
+    }
 
-        let shape = new_shapes.get(round % new_shapes.len()).unwrap();
-        sim.spawn(shape);
-        while sim.moving_rocks.len() > 0 {
+    /// Spawn the next shape and let it fall until it lands, consuming jets
+    /// from the stream along the way
+    fn drop_rock(&mut self, shapes: &[Shape], stream: &[char]) {
+        let shape = shapes.get(self.round % shapes.len()).unwrap();
+        let mut rock = self.spawn(shape);
+        loop {
             // move horizontally first, then vertically
-            let hori_dir = match stream.get(stream_cur % stream.len()).unwrap() {
+            let hori_dir = match stream.get(self.stream_cur).unwrap() {
                 '<' => Dir::Left,
                 '>' => Dir::Right,
                 _ => unreachable!("Illegal input"),
             };
-            stream_cur += 1;
-            let next = sim.get_next_move(hori_dir);
-            if sim.is_in_bounds(&next) && !sim.is_colliding(&next) {
-                sim.commit_move(next);
+            self.stream_cur = (self.stream_cur + 1) % stream.len();
+            if let Some(next) = self.get_next_move(&rock, hori_dir) {
+                if !self.is_colliding(&next) {
+                    rock = next;
+                }
             }
 
-            let next = sim.get_next_move(Dir::Down);
-            if sim.is_in_bounds(&next) && !sim.is_colliding(&next) {
-                sim.commit_move(next);
-            } else {
-                sim.stabilize_moving_rocks();
+            let next = self.get_next_move(&rock, Dir::Down).unwrap();
+            if self.is_colliding(&next) {
+                self.stabilize(&rock);
+                break;
             }
+            rock = next;
         }
-        round += 1;
+        self.prune();
+        self.round += 1;
     }
+}
+
+/// Height of the tower after the given number of rocks. The rocks loop once
+/// the next shape, the next jet, and the reachable rows all repeat, at which
+/// point the height is extrapolated instead of simulated.
+fn simulate(rounds: usize, stream: &[char], shapes: &[Shape], mut sim: Simulation) -> i64 {
+    let mut detector = CycleDetector::new(
+        |sim: &Simulation| (sim.round % shapes.len(), sim.stream_cur, sim.rows.clone()),
+        |sim: &Simulation| sim.peak + 1,
+    );
 
-    println!("{}", sim.peak + skipped + 1);
+    return detector.run(&mut sim, rounds, |sim| sim.drop_rock(shapes, stream));
+}
+
+/// Height of the tower after dropping every single rock
+fn simulate_directly(rounds: usize, stream: &[char], shapes: &[Shape], mut sim: Simulation) -> i64 {
+    for _ in 0..rounds {
+        sim.drop_rock(shapes, stream);
+    }
+    return sim.peak + 1;
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/17.txt").unwrap();
     let stream: Vec<char> = inputs.lines().next().unwrap().chars().collect();
-    simulate(2022, &stream);
-    simulate(1_000_000_000_000, &stream);
+    let args: Vec<String> = env::args().filter(|arg| arg != "--check").collect();
+    let shapes = match args.get(1) {
+        Some(path) => parse_shapes(&fs::read_to_string(path).unwrap()),
+        None => parse_shapes(DEFAULT_SHAPES),
+    };
+    let arg_or = |i: usize, default: i64| args.get(i).map_or(default, |arg| arg.parse::<i64>().unwrap());
+    let (width, spawn_x, spawn_gap) = (arg_or(2, 7), arg_or(3, 2), arg_or(4, 3));
+    let new_sim = || Simulation::new(width, spawn_x, spawn_gap);
+
+    if env::args().any(|arg| arg == "--check") {
+        assert_eq!(
+            simulate(1_000_000, &stream, &shapes, new_sim()),
+            simulate_directly(1_000_000, &stream, &shapes, new_sim())
+        );
+    }
+    println!("{}", simulate(2022, &stream, &shapes, new_sim()));
+    println!("{}", simulate(1_000_000_000_000, &stream, &shapes, new_sim()));
 }
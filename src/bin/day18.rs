@@ -1,5 +1,9 @@
 //! Day 18: Boiling Boulders
-use std::collections::{HashSet, VecDeque};
+//! `cargo run --bin day18 obj|stl [all|exterior] > droplet.obj` prints the
+//! droplet as a triangle mesh instead of the answers. `--check` checks that
+//! the meshes and the air pockets add up to the surface areas.
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 use std::fs;
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
@@ -36,8 +40,14 @@ impl Cube {
     }
 }
 
+fn parse_droplets(inputs: &str) -> HashSet<Cube> {
+    return inputs.lines().map(Cube::from_str).collect();
+}
+
 fn get_limits(cubes: &HashSet<Cube>) -> (i32, i32, i32, i32, i32, i32) {
-    let (mut x_min, mut x_max, mut y_min, mut y_max, mut z_min, mut z_max) = (0, 0, 0, 0, 0, 0);
+    let first = cubes.iter().next().unwrap();
+    let (mut x_min, mut x_max, mut y_min, mut y_max, mut z_min, mut z_max) =
+        (first.x, first.x, first.y, first.y, first.z, first.z);
 
     cubes.iter().for_each(|cube| {
         x_min = x_min.min(cube.x);
@@ -61,75 +71,245 @@ fn is_inbound(cube: &Cube, bounds: (i32, i32, i32, i32, i32, i32)) -> bool {
         && cube.z <= z_max;
 }
 
-/// is outside if it can gradually expand out of bounds
-fn is_outside(
+/// All cubes connected to start without going through a droplet or out of
+/// bounds
+fn flood_fill(
     start: &Cube,
     droplets: &HashSet<Cube>,
-    memo: &mut HashSet<Cube>,
-    boundary: (i32, i32, i32, i32, i32, i32),
-) -> bool {
-    if droplets.contains(start) {
-        return false;
-    }
-    if memo.contains(start) {
-        return true;
-    } // memoization
+    bounds: (i32, i32, i32, i32, i32, i32),
+) -> HashSet<Cube> {
     let mut footprints: HashSet<Cube> = HashSet::new();
     let mut backlog = VecDeque::new(); // push_back and pop_front
     backlog.push_back(start.clone());
     footprints.insert(start.clone());
 
     while let Some(next_cube) = backlog.pop_front() {
-        if !is_inbound(&next_cube, boundary) {
-            memo.insert(next_cube.clone());
-            for connected_cube in footprints.iter() {
-                memo.insert(connected_cube.clone());
-            }
-            return true;
-        }
         for neighbor in next_cube.neighbors() {
-            if !footprints.contains(&neighbor) && !droplets.contains(&neighbor) {
-                backlog.push_back(neighbor.clone());
+            if is_inbound(&neighbor, bounds)
+                && !footprints.contains(&neighbor)
+                && !droplets.contains(&neighbor)
+            {
+                footprints.insert(neighbor.clone());
+                backlog.push_back(neighbor);
             }
-            footprints.insert(neighbor.clone());
         }
     }
+    return footprints;
+}
 
-    return false;
+/// The air outside the droplets: the bounding box is padded by one unit so
+/// that the air can flow all the way around, then flooded from a corner
+fn exterior(droplets: &HashSet<Cube>) -> HashSet<Cube> {
+    let (x_min, x_max, y_min, y_max, z_min, z_max) = get_limits(droplets);
+    let padded = (x_min - 1, x_max + 1, y_min - 1, y_max + 1, z_min - 1, z_max + 1);
+    return flood_fill(&Cube::new(x_min - 1, y_min - 1, z_min - 1), droplets, padded);
 }
 
-fn external_surface_area(inputs: &str, external_only: bool) {
-    let mut droplets = HashSet::new();
-    inputs.lines().for_each(|line| {
-        droplets.insert(Cube::from_str(line));
-    });
-    let mut memo = HashSet::new();
+/// Number of faces of the cubes that face a cube in "other"
+fn count_faces(cubes: &HashSet<Cube>, other: &HashSet<Cube>) -> i32 {
+    return cubes
+        .iter()
+        .map(|cube| {
+            cube.neighbors()
+                .iter()
+                .filter(|neighbor| other.contains(neighbor))
+                .count() as i32
+        })
+        .sum::<i32>();
+}
 
-    let surface_area = droplets
+/// Faces that touch air, or only the ones that touch the given exterior
+fn surface_area(droplets: &HashSet<Cube>, exterior: Option<&HashSet<Cube>>) -> i32 {
+    if let Some(outside) = exterior {
+        return count_faces(droplets, outside);
+    }
+    return droplets
         .iter()
         .map(|cube| {
-            return cube
-                .neighbors()
+            cube.neighbors()
                 .iter()
-                .map(|neighbor| {
-                    if !external_only && !droplets.contains(neighbor) {
-                        return 1;
-                    }
-                    if external_only
-                        && is_outside(neighbor, &droplets, &mut memo, get_limits(&droplets))
-                    {
-                        return 1;
-                    }
-                    return 0;
-                })
-                .sum::<i32>();
+                .filter(|neighbor| !droplets.contains(neighbor))
+                .count() as i32
         })
         .sum::<i32>();
-    println!("{}", surface_area);
+}
+
+/// A connected body of air that is sealed off from the outside
+struct Pocket {
+    voxels: HashSet<Cube>,
+    surface_area: i32, // faces between the pocket and the lava
+}
+
+/// Every air cube inside the bounding box that the exterior does not reach
+/// belongs to a pocket; group them by flooding each one
+fn air_pockets(droplets: &HashSet<Cube>, outside: &HashSet<Cube>) -> Vec<Pocket> {
+    let bounds = get_limits(droplets);
+    let (x_min, x_max, y_min, y_max, z_min, z_max) = bounds;
+    let mut seen: HashSet<Cube> = HashSet::new();
+    let mut pockets = vec![];
+
+    for x in x_min..=x_max {
+        for y in y_min..=y_max {
+            for z in z_min..=z_max {
+                let cube = Cube::new(x, y, z);
+                if droplets.contains(&cube) || outside.contains(&cube) || seen.contains(&cube) {
+                    continue;
+                }
+                let voxels = flood_fill(&cube, droplets, bounds);
+                seen.extend(voxels.iter().cloned());
+                let surface_area = count_faces(&voxels, droplets);
+                pockets.push(Pocket { voxels, surface_area });
+            }
+        }
+    }
+    return pockets;
+}
+
+type Vertex = (i32, i32, i32);
+
+/// The unit normals of the six faces of a cube
+const NORMALS: [Vertex; 6] = [
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// Corners of the face of the cube that points toward "normal". The cube
+/// occupies [x, x + 1] x [y, y + 1] x [z, z + 1], and the corners go
+/// counter-clockwise when looking at the face from outside, which is the
+/// winding that both OBJ and STL viewers expect.
+fn face_corners(cube: &Cube, normal: Vertex) -> [Vertex; 4] {
+    let (x, y, z) = (cube.x, cube.y, cube.z);
+    return match normal {
+        (-1, 0, 0) => [(x, y, z), (x, y, z + 1), (x, y + 1, z + 1), (x, y + 1, z)],
+        (1, 0, 0) => [(x + 1, y, z), (x + 1, y + 1, z), (x + 1, y + 1, z + 1), (x + 1, y, z + 1)],
+        (0, -1, 0) => [(x, y, z), (x + 1, y, z), (x + 1, y, z + 1), (x, y, z + 1)],
+        (0, 1, 0) => [(x, y + 1, z), (x, y + 1, z + 1), (x + 1, y + 1, z + 1), (x + 1, y + 1, z)],
+        (0, 0, 1) => [(x, y, z + 1), (x + 1, y, z + 1), (x + 1, y + 1, z + 1), (x, y + 1, z + 1)],
+        (0, 0, -1) => [(x, y, z), (x, y + 1, z), (x + 1, y + 1, z), (x + 1, y, z)],
+        _ => unreachable!("not a unit normal"),
+    };
+}
+
+/// Every visible face as (normal, corners): faces that touch air, or only the
+/// ones that touch the given exterior
+fn mesh_faces(droplets: &HashSet<Cube>, exterior: Option<&HashSet<Cube>>) -> Vec<(Vertex, [Vertex; 4])> {
+    let mut cubes: Vec<&Cube> = droplets.iter().collect();
+    cubes.sort_by_key(|cube| (cube.x, cube.y, cube.z)); // stable output
+    let mut faces = vec![];
+
+    for cube in cubes {
+        for normal in NORMALS {
+            let (dx, dy, dz) = normal;
+            let neighbor = Cube::new(cube.x + dx, cube.y + dy, cube.z + dz);
+            let is_visible = match exterior {
+                Some(outside) => outside.contains(&neighbor),
+                None => !droplets.contains(&neighbor),
+            };
+            if is_visible {
+                faces.push((normal, face_corners(cube, normal)));
+            }
+        }
+    }
+    return faces;
+}
+
+/// Wavefront OBJ with shared vertices and two triangles per face
+fn to_obj(faces: &[(Vertex, [Vertex; 4])]) -> String {
+    let mut indices: HashMap<Vertex, usize> = HashMap::new();
+    let mut vertices = String::new();
+    let mut triangles = String::new();
+
+    for (_, corners) in faces {
+        let ids: Vec<usize> = corners
+            .iter()
+            .map(|corner| {
+                if let Some(id) = indices.get(corner) {
+                    return *id;
+                }
+                let id = indices.len() + 1; // OBJ counts from 1
+                indices.insert(*corner, id);
+                vertices.push_str(&format!("v {} {} {}\n", corner.0, corner.1, corner.2));
+                return id;
+            })
+            .collect();
+        triangles.push_str(&format!("f {} {} {}\n", ids[0], ids[1], ids[2]));
+        triangles.push_str(&format!("f {} {} {}\n", ids[0], ids[2], ids[3]));
+    }
+    return vertices + &triangles;
+}
+
+/// ASCII STL, two facets per face
+fn to_stl(faces: &[(Vertex, [Vertex; 4])]) -> String {
+    let mut stl = String::from("solid droplet\n");
+    for (normal, corners) in faces {
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            stl.push_str(&format!("facet normal {} {} {}\n", normal.0, normal.1, normal.2));
+            stl.push_str("  outer loop\n");
+            for i in triangle {
+                let (x, y, z) = corners[i];
+                stl.push_str(&format!("    vertex {x} {y} {z}\n"));
+            }
+            stl.push_str("  endloop\n");
+            stl.push_str("endfacet\n");
+        }
+    }
+    stl.push_str("endsolid droplet\n");
+    return stl;
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/18.txt").unwrap();
-    external_surface_area(&inputs, false);
-    external_surface_area(&inputs, true);
+    let droplets = parse_droplets(&inputs);
+
+    let args: Vec<String> = env::args().filter(|arg| arg != "--check").collect();
+    if let Some(format) = args.get(1) {
+        let outside = match args.get(2).map(|arg| arg.as_str()) {
+            None | Some("all") => None,
+            Some("exterior") => Some(exterior(&droplets)),
+            Some(other) => panic!("expected all or exterior, got {other}"),
+        };
+        let faces = mesh_faces(&droplets, outside.as_ref());
+        match format.as_str() {
+            "obj" => print!("{}", to_obj(&faces)),
+            "stl" => print!("{}", to_stl(&faces)),
+            _ => panic!("expected obj or stl, got {format}"),
+        }
+        return;
+    }
+
+    // the exterior is flooded once and shared by everything below
+    let outside = exterior(&droplets);
+    let total = surface_area(&droplets, None);
+    let external = surface_area(&droplets, Some(&outside));
+    println!("{}", total);
+    println!("{}", external);
+
+    let pockets = air_pockets(&droplets, &outside);
+    for pocket in &pockets {
+        let corner = pocket
+            .voxels
+            .iter()
+            .map(|cube| (cube.x, cube.y, cube.z))
+            .min()
+            .unwrap();
+        println!(
+            "pocket at {corner:?}: {} voxels, surface area {}",
+            pocket.voxels.len(),
+            pocket.surface_area
+        );
+    }
+    if env::args().any(|arg| arg == "--check") {
+        // the meshes should have one (two-triangle) face per unit of area
+        assert_eq!(mesh_faces(&droplets, None).len() as i32, total);
+        assert_eq!(mesh_faces(&droplets, Some(&outside)).len() as i32, external);
+        // every face is either on the outside or inside some pocket
+        assert_eq!(
+            total,
+            external + pockets.iter().map(|pocket| pocket.surface_area).sum::<i32>()
+        );
+    }
 }
@@ -1,7 +1,18 @@
 //! Day 19: Not Enough Minerals
-use std::collections::HashMap;
+//! Blueprints are independent of each other, so they are evaluated on as
+//! many threads as there are cores; `cargo run --bin day19 -- --check` also
+//! evaluates them one after another and checks that the results agree.
+//! `cargo run --bin day19 -- --plan 2 32` prints the best build plan for
+//! blueprint 2 in 32 minutes instead.
+use std::env;
 use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
+/// Adding a resource means adding it here, to RESOURCES, and to from_name;
+/// everything else works off the index of the resource
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Resource {
     Ore,
     Clay,
@@ -9,224 +20,366 @@ enum Resource {
     Geode,
 }
 
-#[derive(Debug)]
+const RESOURCES: [Resource; 4] = [
+    Resource::Ore,
+    Resource::Clay,
+    Resource::Obsidian,
+    Resource::Geode,
+];
+
+/// The resource whose count is being maximized
+const GOAL: Resource = Resource::Geode;
+
+impl Resource {
+    fn index(&self) -> usize {
+        return *self as usize;
+    }
+
+    fn from_name(name: &str) -> Self {
+        return match name {
+            "ore" => Resource::Ore,
+            "clay" => Resource::Clay,
+            "obsidian" => Resource::Obsidian,
+            "geode" => Resource::Geode,
+            _ => panic!("unknown resource {name}"),
+        };
+    }
+}
+
+/// Amount of each resource, indexed by Resource::index
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
 struct Cost {
-    ore: i32,
-    obsidian: i32,
-    clay: i32,
+    amounts: [i32; RESOURCES.len()],
 }
 
 impl Cost {
-    fn new(ore: i32, obsidian: i32, clay: i32) -> Self {
-        return Self {
-            ore,
-            obsidian,
-            clay,
-        };
+    fn get(&self, resource: &Resource) -> i32 {
+        return self.amounts[resource.index()];
+    }
+
+    /// clause is like "3 ore and 14 clay"
+    fn from_clause(clause: &str) -> Self {
+        let mut cost = Self::default();
+        for amount in clause.split(" and ") {
+            let mut tokens = amount.split_whitespace();
+            let n = tokens.next().unwrap().parse::<i32>().unwrap();
+            let resource = Resource::from_name(tokens.next().unwrap());
+            cost.amounts[resource.index()] += n;
+        }
+        return cost;
     }
 }
 
 #[derive(Debug)]
 struct Blueprint {
-    ore_bots_cost: Cost,
-    clay_bots_cost: Cost,
-    obsidian_bots_cost: Cost,
-    geode_bots_cost: Cost,
+    id: i32,
+    costs: [Cost; RESOURCES.len()], // costs[i] is the cost of a bot that collects resource i
 }
 
 impl Blueprint {
-    fn new(
-        ore_bots_cost: Cost,
-        clay_bots_cost: Cost,
-        obsidian_bots_cost: Cost,
-        geode_bots_cost: Cost,
-    ) -> Self {
-        return Self {
-            ore_bots_cost,
-            clay_bots_cost,
-            obsidian_bots_cost,
-            geode_bots_cost,
-        };
+    fn cost(&self, resource: &Resource) -> &Cost {
+        return &self.costs[resource.index()];
+    }
+
+    /// The most of each resource that can be spent in a single minute, since
+    /// only one bot can be built per minute
+    fn max_spend(&self) -> Cost {
+        let mut max_spend = Cost::default();
+        for cost in &self.costs {
+            for resource in RESOURCES {
+                let i = resource.index();
+                max_spend.amounts[i] = max_spend.amounts[i].max(cost.amounts[i]);
+            }
+        }
+        return max_spend;
     }
 
-    fn from_line(line: &str) -> Self {
-        let tokens: Vec<&str> = line.split(" ").collect();
-        let ore_bots_cost = Cost::new(tokens[6].parse::<i32>().unwrap(), 0, 0);
-        let clay_bots_cost = Cost::new(tokens[12].parse::<i32>().unwrap(), 0, 0);
-        let obsidian_bots_cost = Cost::new(
-            tokens[18].parse::<i32>().unwrap(),
-            0,
-            tokens[21].parse::<i32>().unwrap(),
-        );
-        let geode_bots_cost = Cost::new(
-            tokens[27].parse::<i32>().unwrap(),
-            tokens[30].parse::<i32>().unwrap(),
-            0,
-        );
-        return Self::new(
-            ore_bots_cost,
-            clay_bots_cost,
-            obsidian_bots_cost,
-            geode_bots_cost,
-        );
+    /// text is like "Blueprint 1: Each ore robot costs 4 ore. Each ...", and
+    /// may be spread over several lines. Costs are read from each "Each X
+    /// robot costs ..." sentence rather than from fixed word positions.
+    fn from_str(text: &str) -> Self {
+        let (header, body) = text.split_once(':').unwrap();
+        let id = header.trim().trim_start_matches("Blueprint").trim().parse::<i32>().unwrap();
+        let mut costs: [Cost; RESOURCES.len()] = Default::default();
+
+        for sentence in body.split('.') {
+            let sentence = sentence.split_whitespace().collect::<Vec<&str>>().join(" ");
+            if sentence.is_empty() {
+                continue;
+            }
+            let (robot, clause) = sentence
+                .strip_prefix("Each ")
+                .and_then(|rest| rest.split_once(" robot costs "))
+                .unwrap_or_else(|| panic!("cannot parse \"{sentence}\""));
+            costs[Resource::from_name(robot).index()] = Cost::from_clause(clause);
+        }
+        return Self { id, costs };
     }
 }
 
+/// Every blueprint in the inputs, whether each one is on a single line or
+/// spread over several
+fn parse_blueprints(inputs: &str) -> Vec<Blueprint> {
+    return inputs
+        .split("Blueprint")
+        .filter(|text| !text.trim().is_empty())
+        .map(Blueprint::from_str)
+        .collect();
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 struct State {
-    ore_bots: i32,
-    clay_bots: i32,
-    obsidian_bots: i32,
-    geode_bots: i32,
-    ore: i32,
-    clay: i32,
-    obsidian: i32,
-    geode: i32,
+    bots: [i32; RESOURCES.len()],
+    stock: [i32; RESOURCES.len()],
 }
 
 impl State {
-    fn can_build_bot(&self, resource: &Resource, blueprint: &Blueprint) -> bool {
-        let cost = match resource {
-            Resource::Ore => &blueprint.ore_bots_cost,
-            Resource::Clay => &blueprint.clay_bots_cost,
-            Resource::Obsidian => &blueprint.obsidian_bots_cost,
-            Resource::Geode => &blueprint.geode_bots_cost,
+    /// Start with a single bot collecting the given resource
+    fn new(first_bot: Resource) -> Self {
+        let mut bots = [0; RESOURCES.len()];
+        bots[first_bot.index()] = 1;
+        return Self {
+            bots,
+            stock: [0; RESOURCES.len()],
         };
-
-        return self.ore >= cost.ore && self.clay >= cost.clay && self.obsidian >= cost.obsidian;
     }
 
     /// If the current state already has as many or more bots of input resource than the highest
     /// cost of of this resource to build any bot, then you should not build this bot
     fn should_build_bot(&self, resource: &Resource, blueprint: &Blueprint) -> bool {
-        let (max_cost, cur_output) = match resource {
-            Resource::Ore => {
-                let _max_cost = blueprint.ore_bots_cost.ore;
-                let _max_cost = _max_cost.max(blueprint.clay_bots_cost.ore);
-                let _max_cost = _max_cost.max(blueprint.obsidian_bots_cost.ore);
-                let _max_cost = _max_cost.max(blueprint.geode_bots_cost.ore);
-                (_max_cost, self.ore_bots)
-            }
-            Resource::Clay => (blueprint.obsidian_bots_cost.clay, self.clay_bots),
-            Resource::Obsidian => (blueprint.geode_bots_cost.obsidian, self.obsidian_bots),
-            _ => (0, 0),
-        };
-        return cur_output < max_cost;
+        if *resource == GOAL {
+            return true;
+        }
+        return self.bots[resource.index()] < blueprint.max_spend().get(resource);
     }
 
     fn build_bot(&self, resource: &Resource, blueprint: &Blueprint) -> Self {
-        let (cost, bot_deltas) = match resource {
-            Resource::Ore => (&blueprint.ore_bots_cost, (1, 0, 0, 0)),
-            Resource::Clay => (&blueprint.clay_bots_cost, (0, 1, 0, 0)),
-            Resource::Obsidian => (&blueprint.obsidian_bots_cost, (0, 0, 1, 0)),
-            Resource::Geode => (&blueprint.geode_bots_cost, (0, 0, 0, 1)),
-        };
-
-        return State {
-            ore: self.ore + self.ore_bots - cost.ore,
-            clay: self.clay + self.clay_bots - cost.clay,
-            obsidian: self.obsidian + self.obsidian_bots - cost.obsidian,
-            geode: self.geode + self.geode_bots,
-            ore_bots: self.ore_bots + bot_deltas.0,
-            clay_bots: self.clay_bots + bot_deltas.1,
-            obsidian_bots: self.obsidian_bots + bot_deltas.2,
-            geode_bots: self.geode_bots + bot_deltas.3,
-        };
+        let cost = blueprint.cost(resource);
+        let mut state = self.idle();
+        for (i, amount) in cost.amounts.iter().enumerate() {
+            state.stock[i] -= amount;
+        }
+        state.bots[resource.index()] += 1;
+        return state;
     }
 
     fn idle(&self) -> Self {
-        return State {
-            ore: self.ore + self.ore_bots,
-            clay: self.clay + self.clay_bots,
-            obsidian: self.obsidian + self.obsidian_bots,
-            geode: self.geode + self.geode_bots,
-            ore_bots: self.ore_bots,
-            clay_bots: self.clay_bots,
-            obsidian_bots: self.obsidian_bots,
-            geode_bots: self.geode_bots,
-        };
+        let mut state = self.clone();
+        for (i, bots) in self.bots.iter().enumerate() {
+            state.stock[i] += bots;
+        }
+        return state;
+    }
+
+    /// Minutes of collecting until the bot can be afforded, or None if the
+    /// bot needs a resource that no bot is collecting
+    fn minutes_to_afford(&self, resource: &Resource, blueprint: &Blueprint) -> Option<i32> {
+        let cost = blueprint.cost(resource);
+        let mut wait = 0;
+        for i in 0..RESOURCES.len() {
+            let (need, have, bots) = (cost.amounts[i], self.stock[i], self.bots[i]);
+            if need > have {
+                if bots == 0 {
+                    return None;
+                }
+                wait = wait.max((need - have + bots - 1) / bots);
+            }
+        }
+        return Some(wait);
+    }
+
+    /// Collect for "wait" minutes, then spend one more minute building the bot
+    fn wait_and_build(&self, wait: i32, resource: &Resource, blueprint: &Blueprint) -> Self {
+        let mut state = self.clone();
+        for _ in 0..wait {
+            state = state.idle();
+        }
+        return state.build_bot(resource, blueprint);
+    }
+
+    /// Anything above what can be spent in the remaining time is useless, so
+    /// throw it away; states that only differ in useless resources are then
+    /// the same
+    fn cap(&self, blueprint: &Blueprint, t_remain: i32) -> Self {
+        let max_spend = blueprint.max_spend();
+        let mut state = self.clone();
+        for resource in RESOURCES {
+            if resource != GOAL {
+                let i = resource.index();
+                state.stock[i] = state.stock[i].min(max_spend.amounts[i] * t_remain);
+            }
+        }
+        return state;
+    }
+
+    /// Most of the goal resource that could be collected if every minute
+    /// brought a free bot of every other kind, plus a goal bot whenever the
+    /// stock covers one
+    fn upper_bound(&self, blueprint: &Blueprint, t_remain: i32) -> i32 {
+        let cost = blueprint.cost(&GOAL);
+        let mut state = self.clone();
+        for _ in 0..t_remain {
+            let can_build_goal_bot = (0..RESOURCES.len()).all(|i| state.stock[i] >= cost.amounts[i]);
+            let mut next = if can_build_goal_bot {
+                state.build_bot(&GOAL, blueprint)
+            } else {
+                state.idle()
+            };
+            for resource in RESOURCES {
+                if resource != GOAL {
+                    next.bots[resource.index()] += 1;
+                }
+            }
+            state = next;
+        }
+        return state.stock[GOAL.index()];
     }
 }
 
-/// Given the starting state, return the maximum number of geode that can be
-/// obtained given the remaining amount of time
-fn dfs(
-    start: State,
+/// Instead of deciding what to do every minute, decide which bot to build
+/// next and skip straight to the minute it can be afforded. Return the most
+/// geodes that can be opened and the builds that get there, as (minutes
+/// remaining when the build starts, bot).
+fn search(
+    start: &State,
     blueprint: &Blueprint,
     t_remain: i32,
-    memo: &mut HashMap<(State, i32), i32>,
     gmax: &mut i32,
-) -> i32 {
-    if let Some(max_geode) = memo.get(&(start.clone(), t_remain)) {
-        return *max_geode;
-    }
-    if t_remain <= 0 {
-        *gmax = (*gmax).max(start.geode);
-        return start.geode;
-    }
-    let max_potential = start.geode + (start.geode_bots + start.geode_bots + t_remain - 1) * t_remain / 2; 
-    if max_potential < *gmax {
-        return start.geode;
-    }
-    // you can: do nothing, build an ore bot, build a clay bot, build an
-    // obsidian bot, build a geode bot
-    let mut max_geode = start.geode;
-
-    if start.can_build_bot(&Resource::Geode, blueprint) {
-        let next_state = start.build_bot(&Resource::Geode, blueprint);
-        max_geode = max_geode.max(dfs(next_state, blueprint, t_remain - 1, memo, gmax));
-        return max_geode;
-    }
-    for resource in [Resource::Obsidian, Resource::Ore, Resource::Clay] {
-        if start.can_build_bot(&resource, blueprint) 
-            && start.should_build_bot(&resource, blueprint)
-        {
-            let next_state = start.build_bot(&resource, blueprint);
-            max_geode = max_geode.max(dfs(next_state, blueprint, t_remain - 1, memo, gmax));
-        }
-    }
-    max_geode = max_geode.max(dfs(start.idle(), blueprint, t_remain - 1, memo, gmax));
-    memo.insert((start, t_remain), max_geode);
-    return max_geode;
+) -> (i32, Vec<(i32, Resource)>) {
+    // building nothing else is always an option
+    let goal = GOAL.index();
+    let mut best = (start.stock[goal] + start.bots[goal] * t_remain, vec![]);
+    *gmax = (*gmax).max(best.0);
+    if start.upper_bound(blueprint, t_remain) <= *gmax {
+        return best;
+    }
+
+    // try the most valuable bots first so that gmax goes up quickly
+    for resource in RESOURCES.iter().rev() {
+        if !start.should_build_bot(resource, blueprint) {
+            continue;
+        }
+        let wait = match start.minutes_to_afford(resource, blueprint) {
+            Some(wait) => wait,
+            None => continue,
+        };
+        // the new bot needs at least one minute left to collect anything
+        if wait + 1 >= t_remain {
+            continue;
+        }
+        let t_next = t_remain - wait - 1;
+        let next_state = start
+            .wait_and_build(wait, resource, blueprint)
+            .cap(blueprint, t_next);
+        let (geode, builds) = search(&next_state, blueprint, t_next, gmax);
+        if geode > best.0 {
+            let mut plan = vec![(t_remain - wait, *resource)];
+            plan.extend(builds);
+            best = (geode, plan);
+        }
+    }
+    return best;
+}
+
+/// The optimal number of geodes and, for every minute, the bot whose
+/// construction starts in that minute (if any)
+fn best_plan(blueprint: &Blueprint, init_state: &State, minutes: i32) -> (i32, Vec<Option<Resource>>) {
+    let mut gmax = 0;
+    let (geode, builds) = search(init_state, blueprint, minutes, &mut gmax);
+    let mut plan = vec![None; minutes as usize];
+    for (t_remain, resource) in builds {
+        plan[(minutes - t_remain) as usize] = Some(resource);
+    }
+    return (geode, plan);
+}
+
+fn max_geodes(blueprint: &Blueprint, init_state: &State, minutes: i32) -> i32 {
+    let (geode, _) = best_plan(blueprint, init_state, minutes);
+    return geode;
+}
+
+fn evaluate_serial(blueprints: &[Blueprint], init_state: &State, minutes: i32) -> Vec<i32> {
+    return blueprints
+        .iter()
+        .map(|bp| max_geodes(bp, init_state, minutes))
+        .collect();
+}
+
+/// Each worker keeps taking the next blueprint nobody has started on, and
+/// sends the result back so that progress can be reported as it comes in
+fn evaluate_parallel(blueprints: &[Blueprint], init_state: &State, minutes: i32) -> Vec<i32> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(blueprints.len());
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results = vec![0; blueprints.len()];
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= blueprints.len() {
+                    break;
+                }
+                let max_geode = max_geodes(&blueprints[i], init_state, minutes);
+                sender.send((i, max_geode)).unwrap();
+            });
+        }
+        drop(sender); // so that the receiver stops once all workers are done
+
+        for (done, (i, max_geode)) in receiver.iter().enumerate() {
+            eprintln!(
+                "[{}/{}] blueprint {}: {max_geode} geodes in {minutes} minutes",
+                done + 1,
+                blueprints.len(),
+                blueprints[i].id
+            );
+            results[i] = max_geode;
+        }
+    });
+    return results;
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/19.txt").unwrap();
-    let mut blueprints = vec![];
-    for line in inputs.lines() {
-        blueprints.push(Blueprint::from_line(line));
-    }
-
-    let init_state = State {
-        ore: 0,
-        clay: 0,
-        obsidian: 0,
-        geode: 0,
-        ore_bots: 1,
-        clay_bots: 0,
-        obsidian_bots: 0,
-        geode_bots: 0,
-    };
+    let blueprints = parse_blueprints(&inputs);
+    let init_state = State::new(Resource::Ore);
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("--plan") {
+        let id = args.get(2).map_or(1, |arg| arg.parse::<i32>().unwrap());
+        let minutes = args.get(3).map_or(24, |arg| arg.parse::<i32>().unwrap());
+        let blueprint = blueprints.iter().find(|bp| bp.id == id).unwrap();
+        let (geode, plan) = best_plan(blueprint, &init_state, minutes);
+        for (minute, build) in plan.iter().enumerate() {
+            match build {
+                Some(resource) => println!("minute {}: build {resource:?} robot", minute + 1),
+                None => println!("minute {}: wait", minute + 1),
+            }
+        }
+        println!("blueprint {id} opens {geode} geodes in {minutes} minutes");
+        return;
+    }
+    let check = args.iter().any(|arg| arg == "--check");
+
+    let max_geodes = evaluate_parallel(&blueprints, &init_state, 24);
+    if check {
+        assert_eq!(max_geodes, evaluate_serial(&blueprints, &init_state, 24));
+    }
     let quality_score = blueprints
         .iter()
-        .enumerate()
-        .map(|(i, bp)| {
-            let mut memo = HashMap::new();
-            let mut gmax = 0;
-            let max_geode = dfs(init_state.clone(), bp, 24, &mut memo, &mut gmax);
-            return (i as i32 + 1) * max_geode;
-        })
+        .zip(max_geodes.iter())
+        .map(|(bp, max_geode)| bp.id * max_geode)
         .sum::<i32>();
     println!("{quality_score}");
 
-    let mut max_geodes = vec![];
-    blueprints.iter().take(3).enumerate().for_each(|(i, bp)| {
-        let mut memo = HashMap::new();
-        let mut gmax = 0;
-        let max_geode = dfs(init_state.clone(), bp, 32, &mut memo, &mut gmax);
-        max_geodes.push(max_geode);
-    });
-    let mut product = 1;
-    max_geodes.iter().for_each(|max_geode| product *= max_geode);
+    let first_three = &blueprints[..3.min(blueprints.len())];
+    let max_geodes = evaluate_parallel(first_three, &init_state, 32);
+    if check {
+        assert_eq!(max_geodes, evaluate_serial(first_three, &init_state, 32));
+    }
+    let product = max_geodes.iter().product::<i32>();
     println!("{product}");
 }
@@ -1,13 +1,17 @@
-//! Assume there are no duplicate in the number
+//! Day 20: Grove Positioning System
+//! Numbers may repeat; each one is tracked by its index in the input, so
+//! duplicates move independently of each other.
+//! `cargo run --bin day20 -- --check` compares the fast mixing against the
+//! quadratic one first.
+use std::env;
 use std::fs;
 
-fn parse_input(inputs: &str) -> Vec<(usize, i64)> {
-    return inputs.lines().enumerate()
-        .map(|(i, num)| (i, num.parse::<i64>().unwrap())).collect();
+fn parse_input(inputs: &str) -> Vec<i64> {
+    return inputs.lines().map(|num| num.parse::<i64>().unwrap()).collect();
 }
 
 /// move the number at "from" forward by "delta" units. "from" is an index;
-/// "delta" can be negative, 
+/// "delta" can be negative,
 fn move_elem<T: Copy>(arr: &mut Vec<T>, from: usize, delta: i64) {
     // the trick here is to modulo by (len - 1) instead of (len)
     // because the list is circular; moving by (len - 1) will return
@@ -24,43 +28,196 @@ fn find_elem<T: Copy + Eq>(arr: &[T], target: &T) -> Option<usize> {
     return None;
 }
 
-fn mix(inputs: &str, key: i64, rounds: usize) -> i64 {
-    let nums_original: Vec<(usize, i64)> = parse_input(&inputs).iter().map(|(i, x)| (*i, x * key)).collect();
-    let mut nums_mut: Vec<(usize, i64)> = parse_input(&inputs).iter().map(|(i, x)| (*i, x * key)).collect();
+/// The original O(n^2) mixing, kept around to check the blocked list against.
+/// Numbers are tagged with their original index so duplicates stay distinct.
+fn mix_naive(nums: &[i64], rounds: usize) -> Vec<i64> {
+    let nums_original: Vec<(usize, i64)> = nums.iter().copied().enumerate().collect();
+    let mut nums_mut = nums_original.clone();
 
-    let mut sum = 0;
-    for round in 0..rounds {
-        println!("{round}");
+    for _ in 0..rounds {
         nums_original.iter()
             .for_each(|elem| {
                 let (_, num) = elem;
                 let cur_loc = find_elem(&nums_mut, elem).unwrap();
                 move_elem(&mut nums_mut, cur_loc, *num);
             });
+    }
+    return nums_mut.iter().map(|(_, num)| *num).collect();
+}
 
-        let mut zero_loc = 0;
-        nums_mut.iter().enumerate()
-            .for_each(|(i, (_, num))| {
-                if *num == 0 {
-                    zero_loc = i;
-                }
-            });
+/// A list of element ids (the index of each number in the original input)
+/// chopped into blocks of about sqrt(n) ids. Locating, removing, and
+/// inserting an id each touch one block plus the block lengths, so a move
+/// costs O(sqrt(n)) instead of O(n).
+///
+/// Blocks are never split or dropped between rebuilds, so "block_of" stays
+/// valid; instead the whole list is re-chunked every "block_size" inserts,
+/// which keeps every block under 2 * block_size ids.
+struct BlockList {
+    blocks: Vec<Vec<usize>>,
+    block_of: Vec<usize>, // block_of[id] is the index of the block holding id
+    block_size: usize,
+    inserts: usize, // number of inserts since the last rebuild
+}
+
+impl BlockList {
+    /// The ids 0..len in order
+    fn new(len: usize) -> Self {
+        let block_size = ((len as f64).sqrt() as usize).max(1);
+        let mut list = Self {
+            blocks: vec![(0..len).collect()],
+            block_of: vec![0; len],
+            block_size,
+            inserts: 0,
+        };
+        list.rebuild();
+        return list;
+    }
+
+    fn len(&self) -> usize {
+        return self.block_of.len();
+    }
+
+    /// Re-chunk the ids into evenly sized blocks
+    fn rebuild(&mut self) {
+        let ids = self.to_vec();
+        self.blocks = ids.chunks(self.block_size)
+            .map(|chunk| chunk.to_vec())
+            .collect();
+        for (b, block) in self.blocks.iter().enumerate() {
+            for id in block {
+                self.block_of[*id] = b;
+            }
+        }
+        self.inserts = 0;
+    }
+
+    /// Current index of the id within the whole list
+    fn position(&self, id: usize) -> usize {
+        let b = self.block_of[id];
+        let before: usize = self.blocks[..b].iter().map(|block| block.len()).sum();
+        let within = self.blocks[b].iter().position(|x| *x == id).unwrap();
+        return before + within;
+    }
+
+    fn remove(&mut self, mut pos: usize) -> usize {
+        for block in self.blocks.iter_mut() {
+            if pos < block.len() {
+                return block.remove(pos);
+            }
+            pos -= block.len();
+        }
+        panic!("position out of bounds");
+    }
+
+    /// Insert the id such that it ends up at "pos"; "pos" can be one past
+    /// the last element
+    fn insert(&mut self, mut pos: usize, id: usize) {
+        let last = self.blocks.len() - 1;
+        for (b, block) in self.blocks.iter_mut().enumerate() {
+            if pos <= block.len() && (pos < block.len() || b == last) {
+                block.insert(pos, id);
+                self.block_of[id] = b;
+                break;
+            }
+            pos -= block.len();
+        }
+        self.inserts += 1;
+        if self.inserts >= self.block_size {
+            self.rebuild();
+        }
+    }
+
+    fn to_vec(&self) -> Vec<usize> {
+        return self.blocks.concat();
+    }
+}
 
-        let keypoints = [1000, 2000, 3000];
-        sum = keypoints.iter()
-            .map(|p| {
-                nums_mut.get((zero_loc + p) % nums_mut.len()).unwrap().1
-            })
-            .sum::<i64>();
+/// Mix the numbers and return the ids (original indices) in their final
+/// order. Ids are what get moved around, so duplicate values are fine.
+fn mix_ids(nums: &[i64], rounds: usize) -> Vec<usize> {
+    let mut list = BlockList::new(nums.len());
+    if nums.len() < 2 {
+        return list.to_vec(); // nothing can move
+    }
+
+    for _ in 0..rounds {
+        for (id, num) in nums.iter().enumerate() {
+            // same (len - 1) trick as in move_elem
+            let from = list.position(id);
+            let dst = (from as i64 + num).rem_euclid(list.len() as i64 - 1);
+            let removed = list.remove(from);
+            list.insert(dst as usize, removed);
+        }
+    }
+    return list.to_vec();
+}
+
+/// The full mixed sequence of values
+fn mix(nums: &[i64], rounds: usize) -> Vec<i64> {
+    return mix_ids(nums, rounds).iter().map(|id| nums[*id]).collect();
+}
+
+/// The values found at each offset after the zero, wrapping around. If there
+/// are several zeros, the one that came first in the input is used; None if
+/// there is no zero.
+fn probe(nums: &[i64], rounds: usize, offsets: &[usize]) -> Option<Vec<i64>> {
+    let zero_id = nums.iter().position(|num| *num == 0)?;
+    let order = mix_ids(nums, rounds);
+    let zero_loc = order.iter().position(|id| *id == zero_id).unwrap();
+    return Some(offsets.iter()
+        .map(|offset| nums[order[(zero_loc + offset) % order.len()]])
+        .collect());
+}
+
+fn grove_coordinates(inputs: &str, key: i64, rounds: usize) -> Option<i64> {
+    let nums: Vec<i64> = parse_input(inputs).iter().map(|x| x * key).collect();
+    return probe(&nums, rounds, &[1000, 2000, 3000]).map(|values| values.iter().sum::<i64>());
+}
+
+/// Tiny linear congruential generator so that the checks below do not need
+/// an external crate
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return self.0 >> 33;
+    }
+}
+
+/// Compare the blocked list against brute force on the given inputs and on
+/// generated lists that are short and full of duplicates
+fn check(inputs: &[&str]) {
+    for inputs in inputs {
+        let nums = parse_input(inputs);
+        assert_eq!(mix(&nums, 1), mix_naive(&nums, 1));
+        let nums: Vec<i64> = nums.iter().map(|x| x * 811589153).collect();
+        assert_eq!(mix(&nums, 10), mix_naive(&nums, 10));
+    }
+
+    let mut rng = Lcg(2022);
+    for _ in 0..500 {
+        let len = 2 + (rng.next() % 60) as usize;
+        let nums: Vec<i64> = (0..len)
+            .map(|_| (rng.next() % 11) as i64 - 5)
+            .collect();
+        let rounds = 1 + (rng.next() % 3) as usize;
+        assert_eq!(mix(&nums, rounds), mix_naive(&nums, rounds));
     }
-    return sum;
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/20.txt").unwrap();
-    let sum = mix(&inputs, 1, 1);
-    println!("{sum}");
+    if env::args().any(|arg| arg == "--check") {
+        let sample = fs::read_to_string("inputs/20.test").unwrap();
+        check(&[&sample, &inputs]);
+    }
 
-    let sum = mix(&inputs, 811589153, 10);
-    println!("{sum}");
+    for (key, rounds) in [(1, 1), (811589153, 10)] {
+        match grove_coordinates(&inputs, key, rounds) {
+            Some(sum) => println!("{sum}"),
+            None => println!("there is no 0 in the input"),
+        }
+    }
 }
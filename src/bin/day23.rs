@@ -1,3 +1,13 @@
+//! Day 23: Unstable Diffusion
+//! `cargo run --bin day23 -- --check` compares the dense engine against the
+//! original hash set one, round by round, before solving.
+//!
+//! The movement rules can be changed to explore other ways for the elves to
+//! spread: `--diagonal` lets them move diagonally too, `--fixed` keeps the
+//! order of directions the same every round instead of rotating it, and
+//! `--rules "N=NW,N,NE S=SW,S,SE"` gives the directions in order, each with
+//! the cells that block it.
+use std::env;
 use std::fs;
 use std::collections::{ HashSet, HashMap };
 
@@ -12,64 +22,155 @@ impl Point {
     }
 }
 
-#[derive(Debug,Clone,Hash,Eq,PartialEq)]
+#[derive(Debug,Clone,Hash,Eq,PartialEq,Ord,PartialOrd)]
 enum Dir {
-    N, S, W, E,
+    N, S, W, E, NE, SE, SW, NW,
 }
 
-#[derive(Debug)]
-struct Group {
-    elves: HashSet<Point>,
+impl Dir {
+    /// The eight neighbors, clockwise from north
+    const ALL: [Dir; 8] = [Dir::N, Dir::NE, Dir::E, Dir::SE, Dir::S, Dir::SW, Dir::W, Dir::NW];
+
+    fn delta(&self) -> (i32, i32) {
+        return match self {
+            Dir::N => (0, -1),
+            Dir::S => (0, 1),
+            Dir::W => (-1, 0),
+            Dir::E => (1, 0),
+            Dir::NE => (1, -1),
+            Dir::SE => (1, 1),
+            Dir::SW => (-1, 1),
+            Dir::NW => (-1, -1),
+        };
+    }
+
+    fn from_name(name: &str) -> Self {
+        return match name {
+            "N" => Dir::N,
+            "S" => Dir::S,
+            "W" => Dir::W,
+            "E" => Dir::E,
+            "NE" => Dir::NE,
+            "SE" => Dir::SE,
+            "SW" => Dir::SW,
+            "NW" => Dir::NW,
+            _ => panic!("Illegal direction {name}"),
+        };
+    }
 }
 
-impl Group {
-    fn new() -> Self {
-        let elves = HashSet::new();
+/// The puzzle: an elf moves toward a side only if the three cells on that
+/// side are empty
+const STANDARD_RULES: &str = "N=NW,N,NE S=SW,S,SE W=NW,W,SW E=NE,E,SE";
+/// The puzzle plus diagonal moves, each blocked by the corner and the two
+/// cells next to it
+const DIAGONAL_RULES: &str = "N=NW,N,NE S=SW,S,SE W=NW,W,SW E=NE,E,SE NE=N,NE,E SE=S,SE,E SW=S,SW,W NW=N,NW,W";
+
+/// A direction an elf may move in and the cells that have to be empty for it
+#[derive(Debug,Clone)]
+struct Rule {
+    dir: Dir,
+    blockers: Vec<Dir>,
+}
+
+/// Which direction comes first on a given round
+#[derive(Debug,Clone)]
+enum Rotation {
+    ByRound, // the first direction moves to the back after every round
+    Fixed,   // always the same order
+}
+
+#[derive(Debug,Clone)]
+struct Rules {
+    order: Vec<Rule>,
+    rotation: Rotation,
+}
+
+impl Rules {
+    /// Rules separated by spaces, each a direction, "=", and the cells that
+    /// block it separated by commas, e.g. "N=NW,N,NE". The cells have to
+    /// include the one the elf moves into.
+    fn parse(spec: &str, rotation: Rotation) -> Self {
+        let order: Vec<Rule> = spec.split_whitespace()
+            .map(|rule| {
+                let (dir, blockers) = rule.split_once('=').expect("rules look like N=NW,N,NE");
+                let blockers = blockers.split(',').map(Dir::from_name).collect();
+                return Rule { dir: Dir::from_name(dir), blockers };
+            })
+            .collect();
+        if order.is_empty() {
+            panic!("there has to be at least one rule");
+        }
+        if order.iter().any(|rule| order.iter().filter(|other| other.dir == rule.dir).count() > 1) {
+            panic!("each direction can only have one rule");
+        }
+        // otherwise an elf could move into an elf, which neither engine
+        // resolves
+        if let Some(rule) = order.iter().find(|rule| !rule.blockers.contains(&rule.dir)) {
+            panic!("{:?} has to be blocked by the cell it moves into", rule.dir);
+        }
+        return Self { order, rotation };
+    }
+
+    fn standard() -> Self {
+        return Self::parse(STANDARD_RULES, Rotation::ByRound);
+    }
 
-        return Self { elves };
+    /// Number of rounds after which the order is the same again
+    fn period(&self) -> usize {
+        return match self.rotation {
+            Rotation::ByRound => self.order.len(),
+            Rotation::Fixed => 1,
+        };
     }
 
+    /// For the standard rules,
     /// on round 0 consider N, S, W, E
     /// on round 1 consider S, W, E, N
     /// on round 2 ocnsdier W, E, N, S
     /// on round 3 consider E, N, S, W
-    fn propose_next(&self, elf: &Point, round: usize) -> (Point, Option<Dir>) {
-        let (mut north_empty, mut south_empty, mut west_empty, mut east_empty) = (true, true, true, true);
-        for (x_delta, y_delta) in [
-            (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)
-        ] {
-            let next_pos = Point::new(elf.x + x_delta, elf.y + y_delta);
-            if self.elves.get(&next_pos).is_some() {
-                // found an adjacent elf
-                if y_delta == -1 { north_empty = false; }
-                if y_delta == 1 { south_empty = false; }
-                if x_delta == 1 { east_empty = false; }
-                if x_delta == -1 { west_empty = false; }
-            }
-        }
+    fn in_order(&self, round: usize) -> impl Iterator<Item = &Rule> {
+        let first = round % self.period();
+        return (0..self.order.len()).map(move |i| &self.order[(first + i) % self.order.len()]);
+    }
 
-        if north_empty && south_empty && west_empty && east_empty {
-            return (elf.clone(), None);
+    /// The direction an elf moves in, given which of its neighbors are
+    /// empty; None if it has no neighbors at all or every way is blocked
+    fn propose(&self, round: usize, is_empty: impl Fn(&Dir) -> bool) -> Option<Dir> {
+        if Dir::ALL.iter().all(&is_empty) {
+            return None;
         }
-        for i in round..=round+3 {
-            let j = i.rem_euclid(4);
-            match j {
-                0 if north_empty => {
-                    return (Point::new(elf.x, elf.y - 1), Some(Dir::N));
-                },
-                1 if south_empty => {
-                    return (Point::new(elf.x, elf.y + 1), Some(Dir::S));
-                },
-                2 if west_empty => {
-                    return (Point::new(elf.x - 1, elf.y), Some(Dir::W));
-                },
-                3 if east_empty => {
-                    return (Point::new(elf.x + 1, elf.y), Some(Dir::E));
-                },
-                _ => (),
-            }
-        }
-        return (elf.clone(), None);
+        return self.in_order(round)
+            .find(|rule| rule.blockers.iter().all(&is_empty))
+            .map(|rule| rule.dir.clone());
+    }
+}
+
+#[derive(Debug)]
+struct Group {
+    elves: HashSet<Point>,
+    rules: Rules,
+}
+
+impl Group {
+    fn new(rules: Rules) -> Self {
+        let elves = HashSet::new();
+
+        return Self { elves, rules };
+    }
+
+    fn propose_next(&self, elf: &Point, round: usize) -> (Point, Option<Dir>) {
+        let dir = self.rules.propose(round, |dir| {
+            let (x_delta, y_delta) = dir.delta();
+            return !self.elves.contains(&Point::new(elf.x + x_delta, elf.y + y_delta));
+        });
+        return match dir {
+            Some(dir) => {
+                let (x_delta, y_delta) = dir.delta();
+                (Point::new(elf.x + x_delta, elf.y + y_delta), Some(dir))
+            },
+            None => (elf.clone(), None),
+        };
     }
 
     /// This time I will immutably step, also return the number of elves that moved
@@ -93,7 +194,7 @@ impl Group {
                 }
             });
 
-        let mut new_group = Self::new();
+        let mut new_group = Self::new(self.rules.clone());
         moves.iter()
             .for_each(|(old_pos, new_pos)| {
                 if *counts.get(new_pos).unwrap() == 1 {  // must have been inserted before
@@ -160,8 +261,17 @@ impl Group {
         return count;
     }
 
-    fn from_inputs(inputs: &str) -> Self {
-        let mut group = Group::new();
+    /// The elves in a fixed order so that the group can be hashed
+    fn sorted_elves(&self) -> Vec<(i32, i32)> {
+        let mut elves: Vec<(i32, i32)> = self.elves.iter()
+            .map(|elf| (elf.x, elf.y))
+            .collect();
+        elves.sort();
+        return elves;
+    }
+
+    fn from_inputs(inputs: &str, rules: &Rules) -> Self {
+        let mut group = Group::new(rules.clone());
         for (y, line) in inputs.lines().enumerate() {
             for (x, c) in line.chars().enumerate() {
                 if c == '#' {
@@ -173,26 +283,206 @@ impl Group {
     }
 }
 
+/// Same rules as Group, but the elves live on a dense grid that grows with
+/// them, so looking around is indexing instead of hashing. Every elf that
+/// could propose a cell is next to it, and only one of them can get there by
+/// a given direction, so a proposal is rejected exactly when a neighbor of
+/// the cell proposed it by another direction; no counting of proposals is
+/// needed. Without diagonal moves that is the elf two cells ahead proposing
+/// the opposite direction.
+struct Grove {
+    x_min: i32, y_min: i32, // coordinates of cell 0
+    width: usize, height: usize,
+    taken: Vec<bool>,
+    proposed: Vec<Option<Dir>>, // what the elf in the cell proposed this round
+    elves: Vec<Point>,
+    rules: Rules,
+}
+
+impl Grove {
+    /// Empty cells kept around the elves when the grid is rebuilt
+    const MARGIN: i32 = 16;
+
+    fn from_group(group: &Group) -> Self {
+        let mut grove = Self {
+            x_min: 0, y_min: 0, width: 0, height: 0,
+            taken: vec![], proposed: vec![],
+            elves: group.elves.iter().cloned().collect(),
+            rules: group.rules.clone(),
+        };
+        grove.rebuild();
+        return grove;
+    }
+
+    fn to_group(&self) -> Group {
+        return Group { elves: self.elves.iter().cloned().collect(), rules: self.rules.clone() };
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        return (y - self.y_min) as usize * self.width + (x - self.x_min) as usize;
+    }
+
+    /// Size the grid to the elves plus the margin, which grows with the
+    /// elves so that a group that keeps spreading is not rebuilt every few
+    /// rounds
+    fn rebuild(&mut self) {
+        let (x_min, x_max, y_min, y_max) = self.to_group().get_bounds();
+        let margin = Self::MARGIN.max((x_max - x_min).max(y_max - y_min) / 2);
+        self.x_min = x_min - margin;
+        self.y_min = y_min - margin;
+        self.width = (x_max - x_min + 1 + 2 * margin) as usize;
+        self.height = (y_max - y_min + 1 + 2 * margin) as usize;
+        self.taken = vec![false; self.width * self.height];
+        self.proposed = vec![None; self.width * self.height];
+        for elf in &self.elves {
+            let at = self.index(elf.x, elf.y);
+            self.taken[at] = true;
+        }
+    }
+
+    /// An elf looks up to two cells away when resolving its proposal, so every elf
+    /// must stay at least two cells away from the edge of the grid
+    fn fits(&self) -> bool {
+        return self.elves.iter().all(|elf| {
+            elf.x - self.x_min >= 2 && elf.y - self.y_min >= 2
+                && (self.x_min + self.width as i32 - 1) - elf.x >= 2
+                && (self.y_min + self.height as i32 - 1) - elf.y >= 2
+        });
+    }
+
+    fn propose(&self, elf: &Point, round: usize) -> Option<Dir> {
+        return self.rules.propose(round, |dir| {
+            let (x_delta, y_delta) = dir.delta();
+            return !self.taken[self.index(elf.x + x_delta, elf.y + y_delta)];
+        });
+    }
+
+    /// Move the elves in place, return the number of moves the way
+    /// Group::step counts them, i.e. the number of cells proposed by elves
+    /// that want to move
+    fn step(&mut self, round: usize) -> usize {
+        if !self.fits() {
+            self.rebuild();
+        }
+        let proposals: Vec<Option<Dir>> = self.elves.iter()
+            .map(|elf| self.propose(elf, round))
+            .collect();
+        for (elf, dir) in self.elves.iter().zip(&proposals) {
+            let at = self.index(elf.x, elf.y);
+            self.proposed[at] = dir.clone();
+        }
+
+        let mut count_moves = 0;
+        let mut moves = vec![];
+        for (i, dir) in proposals.iter().enumerate() {
+            let Some(dir) = dir else { continue };
+            let (x_delta, y_delta) = dir.delta();
+            let elf = &self.elves[i];
+            let next_pos = Point::new(elf.x + x_delta, elf.y + y_delta);
+            // the other elves that proposed the same cell, by their direction
+            let rivals: Vec<&Dir> = self.rules.order.iter()
+                .map(|rule| &rule.dir)
+                .filter(|other| *other != dir)
+                .filter(|other| {
+                    let (x_delta, y_delta) = other.delta();
+                    return self.proposed[self.index(next_pos.x - x_delta, next_pos.y - y_delta)].as_ref() == Some(*other);
+                })
+                .collect();
+            if !rivals.is_empty() {
+                // the cell still counts once
+                if rivals.iter().all(|other| *other > dir) {
+                    count_moves += 1;
+                }
+            } else {
+                count_moves += 1;
+                moves.push((i, next_pos));
+            }
+        }
+
+        for elf in &self.elves {
+            let at = self.index(elf.x, elf.y);
+            self.proposed[at] = None;
+        }
+        for (i, next_pos) in moves {
+            let (old_at, new_at) = (self.index(self.elves[i].x, self.elves[i].y), self.index(next_pos.x, next_pos.y));
+            self.taken[old_at] = false;
+            self.taken[new_at] = true;
+            self.elves[i] = next_pos;
+        }
+        return count_moves;
+    }
+}
+
+/// Other rules can keep the elves moving forever, e.g. when they can only
+/// go north, so give up after this many rounds
+const MAX_ROUNDS: usize = 5000;
+
+/// Part 2 with the reference engine: the first round in which nobody moves
+fn settle_with_group(inputs: &str, rules: &Rules) -> Option<usize> {
+    let mut group = Group::from_inputs(inputs, rules);
+    for round in 0..MAX_ROUNDS {
+        let (next_group, nmoves) = group.step(round);
+        if nmoves == 0 {
+            return Some(round + 1);
+        }
+        group = next_group;
+    }
+    return None;
+}
+
+/// Part 2 with the dense engine: the first round in which nobody moves
+fn settle_with_grove(inputs: &str, rules: &Rules) -> Option<usize> {
+    let mut grove = Grove::from_group(&Group::from_inputs(inputs, rules));
+    return (0..MAX_ROUNDS)
+        .find(|round| grove.step(*round) == 0)
+        .map(|round| round + 1);
+}
+
+/// Run both engines side by side and compare them after every round
+fn check(inputs: &str, rules: &Rules) {
+    let mut group = Group::from_inputs(inputs, rules);
+    let mut grove = Grove::from_group(&group);
+    for round in 0..MAX_ROUNDS {
+        let (next_group, group_moves) = group.step(round);
+        group = next_group;
+        let grove_moves = grove.step(round);
+        assert_eq!(group_moves, grove_moves, "round {round}");
+        assert_eq!(group.sorted_elves(), grove.to_group().sorted_elves(), "round {round}");
+        if group_moves == 0 {
+            assert_eq!(settle_with_group(inputs, rules), settle_with_grove(inputs, rules));
+            println!("both engines agree for {} rounds", round + 1);
+            return;
+        }
+    }
+    println!("both engines agree for {MAX_ROUNDS} rounds, and the elves are still moving");
+}
+
 fn main() {
     let inputs = fs::read_to_string("inputs/23.txt").unwrap();
+    let args: Vec<String> = env::args().collect();
+    let rotation = match args.iter().any(|arg| arg == "--fixed") {
+        true => Rotation::Fixed,
+        false => Rotation::ByRound,
+    };
+    let rules = match args.iter().position(|arg| arg == "--rules") {
+        Some(i) => Rules::parse(&args[i + 1], rotation),
+        None if args.iter().any(|arg| arg == "--diagonal") => Rules::parse(DIAGONAL_RULES, rotation),
+        None => Rules { rotation, ..Rules::standard() },
+    };
+    if args.iter().any(|arg| arg == "--check") {
+        check(&inputs, &rules);
+    }
 
     // part 1
-    let mut group = Group::from_inputs(&inputs);
+    let mut grove = Grove::from_group(&Group::from_inputs(&inputs, &rules));
     for round in 0..10 {
-        let (next_group, _) = group.step(round);
-        group = next_group;
+        grove.step(round);
     }
-    println!("{}", group.count_spread());
+    println!("{}", grove.to_group().count_spread());
 
     // part 2
-    let mut group = Group::from_inputs(&inputs);
-    for round in 0.. {
-        let (next_group, nmoves) = group.step(round);
-        if nmoves == 0 {
-            println!("{}", round + 1);
-            break;
-        } else {
-            group = next_group;
-        }
+    match settle_with_grove(&inputs, &rules) {
+        Some(round) => println!("{round}"),
+        None => println!("the elves are still moving after {MAX_ROUNDS} rounds"),
     }
 }
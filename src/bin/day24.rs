@@ -1,6 +1,17 @@
+//! Day 24: Blizzard Basin
+//! `cargo run --bin day24 -- --replay` animates the route of the first trip.
+//! `cargo run --bin day24 -- --trip entrance 5,3 exit` plans a trip through
+//! any waypoints instead of part 2.
+//! `cargo run --bin day24 -- --check` also checks the forecast against
+//! stepping the storms, and replays every route to check it is safe.
+use std::cmp::Reverse;
+use std::env;
+use std::fmt;
 use std::fs;
-use std::collections::{ HashSet, VecDeque };
+use std::collections::{ BinaryHeap, HashSet };
+use std::thread;
 use std::time;
+use std::time::Duration;
 
 #[derive(Debug,Eq,PartialEq,Hash,Clone)]
 struct Point {
@@ -82,12 +93,6 @@ impl Map {
         return Self { storms, walls, cur, stop, shape };
     }
 
-    fn is_in_bounds(&self, pos: &Point) -> bool {
-        let (nrows, ncols) = self.shape;
-        return pos.x >= 0 && pos.x < ncols as i32 
-            && pos.y >= 0 && pos.y < nrows as i32;
-    }
-
     fn render(&self) {
         let (y_max, x_max) = self.shape;
         let mut grid_str = String::new();
@@ -154,26 +159,105 @@ impl Map {
 
         return storms;
     }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        return a;
+    }
+    return gcd(b, a % b);
+}
+
+/// Every blizzard comes back to where it started after (nrows - 2) or
+/// (ncols - 2) minutes, so the whole valley repeats itself every
+/// lcm(nrows - 2, ncols - 2) minutes. The forecast records which cells are
+/// taken (by a wall or a blizzard) at each minute of one period, once, so
+/// that searches only ever need (time mod period, position).
+struct Forecast {
+    period: usize,
+    shape: (usize, usize), // nrows, ncols
+    walls: Vec<bool>, // walls[y * ncols + x]
+    blocked: Vec<Vec<bool>>, // blocked[t][y * ncols + x]
+}
+
+impl Forecast {
+    fn new(map: &Map) -> Self {
+        let (nrows, ncols) = map.shape;
+        let (height, width) = (nrows - 2, ncols - 2);
+        let period = height / gcd(height, width) * width;
+        let mut walls = vec![false; nrows * ncols];
+        for pos in &map.walls {
+            walls[pos.y as usize * ncols + pos.x as usize] = true;
+        }
+        let mut blocked = vec![walls.clone(); period];
+
+        // instead of stepping all storms one minute at a time, place each
+        // storm directly: it moves one cell per minute and wraps around
+        // inside the walls
+        for storm in &map.storms {
+            let (x, y) = (storm.pos.x as usize - 1, storm.pos.y as usize - 1);
+            for (t, cells) in blocked.iter_mut().enumerate() {
+                let (x, y) = match storm.dir {
+                    Dir::Up => (x, (y + height - t % height) % height),
+                    Dir::Down => (x, (y + t) % height),
+                    Dir::Left => ((x + width - t % width) % width, y),
+                    Dir::Right => ((x + t) % width, y),
+                };
+                cells[(y + 1) * ncols + x + 1] = true;
+            }
+        }
+
+        return Self { period, shape: map.shape, walls, blocked };
+    }
 
-    /// Get valid "next_pos" which is defined by "not wall and not storm";
-    /// I will put "down" and "right" first because they are preferable
-    fn get_next_curs(&self) -> Vec<Point> {
+    /// Compare against stepping the storms one minute at a time
+    fn check(&self, map: &Map, minutes: usize) {
+        let mut map = map.clone();
+        for time in 0..minutes {
+            let taken: HashSet<&Point> = map.storms.iter().map(|storm| &storm.pos).collect();
+            let blocked = self.blocked[time % self.period].iter().filter(|b| **b).count();
+            assert_eq!(blocked, taken.len() + map.walls.len());
+            for pos in taken {
+                assert!(!self.is_open(pos, time));
+            }
+            map.storms = map.step_storm();
+        }
+    }
+
+    /// Walls and cells off the map are never open
+    fn is_wall(&self, pos: &Point) -> bool {
+        let (nrows, ncols) = self.shape;
+        if pos.x < 0 || pos.x >= ncols as i32 || pos.y < 0 || pos.y >= nrows as i32 {
+            return true;
+        }
+        return self.walls[pos.y as usize * ncols + pos.x as usize];
+    }
+
+    fn is_open(&self, pos: &Point, time: usize) -> bool {
+        let (nrows, ncols) = self.shape;
+        if pos.x < 0 || pos.x >= ncols as i32 || pos.y < 0 || pos.y >= nrows as i32 {
+            return false;
+        }
+        return !self.blocked[time % self.period][pos.y as usize * ncols + pos.x as usize];
+    }
+
+    /// Get valid "next_pos" which is defined by "not wall and not storm",
+    /// along with the move that gets there (None for waiting). Up and down
+    /// come first, then waiting, then right and left. In a BFS the order does
+    /// not change how long the route takes, only which of several equally
+    /// fast routes is found: the first one to reach the stop in queue order.
+    fn get_next_curs(&self, cur: &Point, time: usize) -> Vec<(Option<Dir>, Point)> {
         let mut next_curs = vec![];
-        let next_storms = self.step_storm(); // 新的风暴已经出现
 
-        for (x_delta, y_delta) in [
-            (0, -1), (0, 1),  // going right and down are preferrable
-            (0, 0),  // 怎么能够停滞不前
-                     // staying put is better than getting further
-            (1, 0), (-1, 0),   // going lef tnad up are not
+        for (dir, x_delta, y_delta) in [
+            (Some(Dir::Up), 0, -1), (Some(Dir::Down), 0, 1),
+            (None, 0, 0),  // 怎么能够停滞不前
+                           // staying put keeps the options open
+            (Some(Dir::Right), 1, 0), (Some(Dir::Left), -1, 0),
         ] {
-            let new_cur = Point::new(self.cur.x + x_delta, self.cur.y + y_delta);
-            if next_storms.iter()
-                .filter(|storm| storm.pos == new_cur)
-                .count() == 0 
-                && !self.walls.contains(&new_cur)
-                && self.is_in_bounds(&new_cur) {
-                next_curs.push(new_cur);
+            let new_cur = Point::new(cur.x + x_delta, cur.y + y_delta);
+            if self.is_open(&new_cur, time + 1) { // 新的风暴已经出现
+                next_curs.push((dir, new_cur));
             }
         }
 
@@ -181,53 +265,335 @@ impl Map {
     }
 }
 
-/// BFS, which means when you reach the destination, it's the best time
-fn bfs(cur_state: &Map, max_time: usize) -> (usize, Map) {
-    let mut backlog: VecDeque<(usize, Map)> = VecDeque::new();
-    let mut footprints: HashSet<(usize, Point)> = HashSet::new();
-    backlog.push_back((0, cur_state.clone()));
-    footprints.insert((0, cur_state.cur.clone()));
-
-    while backlog.len() > 0 {
-        let (top_time, top_map) = backlog.pop_front().unwrap();
-        // println!("{top_time} {:?}", top_map.cur);
-        if top_map.cur == top_map.stop { return (top_time, top_map); }
-        if top_time > max_time {  panic!("ran out of time") }  // all items behind it will
-                                                 // take longer
-        for next_cur in top_map.get_next_curs() {
-            if !footprints.contains(&(top_time + 1, next_cur.clone())) {
-                let mut next_map = top_map.clone();
-                next_map.cur = next_cur.clone();
-                next_map.storms = top_map.step_storm();
-                footprints.insert((top_time + 1, next_cur));
-                backlog.push_back((top_time + 1, next_map));
+/// Which states (minute within the period, position) have been seen. The
+/// same position at the same point of the period is the same state, however
+/// many periods later. On a trip through waypoints, a state that has reached
+/// more of them is better: whatever the other one does from there, it can do
+/// the same, only with more waypoints behind it.
+struct Footprints {
+    period: usize,
+    ncols: usize,
+    seen: Vec<Vec<usize>>, // seen[t][y * ncols + x]: 1 + most waypoints reached, 0 if never
+}
+
+impl Footprints {
+    fn new(forecast: &Forecast) -> Self {
+        let (nrows, ncols) = forecast.shape;
+        let seen = vec![vec![0; nrows * ncols]; forecast.period];
+        return Self { period: forecast.period, ncols, seen };
+    }
+
+    /// Mark the state as seen; false if it had already been seen
+    fn visit(&mut self, time: usize, pos: &Point) -> bool {
+        return self.visit_reached(time, pos, 0);
+    }
+
+    /// Whether no state at the same point of the period has reached more
+    fn is_best(&self, time: usize, pos: &Point, reached: usize) -> bool {
+        return self.seen[time % self.period][pos.y as usize * self.ncols + pos.x as usize] <= reached + 1;
+    }
+
+    /// Mark the state as seen with "reached" waypoints behind it; false if it
+    /// had already been seen with as many or more
+    fn visit_reached(&mut self, time: usize, pos: &Point, reached: usize) -> bool {
+        let cell = &mut self.seen[time % self.period][pos.y as usize * self.ncols + pos.x as usize];
+        if *cell > reached {
+            return false;
+        }
+        *cell = reached + 1;
+        return true;
+    }
+}
+
+/// Why a trip cannot be made
+#[derive(Debug)]
+enum TripError {
+    NoWaypoints,
+    Blocked(Point), // the waypoint is a wall or off the map
+    Unreachable { leg: usize, from: Point, to: Point },
+}
+
+impl fmt::Display for TripError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            TripError::NoWaypoints => write!(f, "a trip needs at least one waypoint"),
+            TripError::Blocked(pos) => write!(f, "({}, {}) is not in the valley", pos.x, pos.y),
+            TripError::Unreachable { leg, from, to } => write!(
+                f, "leg {} from ({}, {}) to ({}, {}) can never be made",
+                leg + 1, from.x, from.y, to.x, to.y
+            ),
+        };
+    }
+}
+
+/// A state reached by a search, with the move and the state it came from
+struct Step {
+    time: usize,
+    pos: Point,
+    reached: usize, // number of waypoints reached after the first one
+    dir: Option<Dir>,
+    prev: Option<usize>, // index of the previous step
+}
+
+/// Follow the links back from the last step and return the moves in order,
+/// one per minute, None meaning wait
+fn trace_route(steps: &[Step], last: usize) -> Vec<Option<Dir>> {
+    let mut route = vec![];
+    let mut cur = last;
+    while let Some(prev) = steps[cur].prev {
+        route.push(steps[cur].dir.clone());
+        cur = prev;
+    }
+    route.reverse();
+    return route;
+}
+
+/// The fastest way through the waypoints, in order
+struct Itinerary {
+    start_time: usize,
+    legs: Vec<usize>, // minutes taken by each leg
+    route: Vec<Option<Dir>>, // one move per minute, None meaning wait
+}
+
+impl Itinerary {
+    fn total(&self) -> usize {
+        return self.route.len();
+    }
+}
+
+/// BFS over (waypoints reached, time mod period, position), which means when
+/// you reach the last waypoint, it's the best time for the whole trip. Legs
+/// are not solved one by one: reaching a waypoint as early as possible is not
+/// always best, since the expedition may not be able to wait there. Leave the
+/// first waypoint at minute "start_time".
+///
+/// There are usually many equally fast trips, and the first one to claim a
+/// state keeps it. The search goes one minute at a time, and the states that
+/// only just reached their latest waypoint go after the ones that got there
+/// earlier, so among trips that have reached as many waypoints, the one
+/// that got to the latest of them first wins.
+///
+/// Every state is visited at most once, so if the search runs dry the next
+/// waypoint can never be reached, whatever the time.
+fn plan_trip(forecast: &Forecast, waypoints: &[Point], start_time: usize) -> Result<Itinerary, TripError> {
+    let start = waypoints.first().ok_or(TripError::NoWaypoints)?;
+    if let Some(pos) = waypoints.iter().find(|pos| forecast.is_wall(pos)) {
+        return Err(TripError::Blocked(pos.clone()));
+    }
+    let last_leg = waypoints.len() - 1;
+    // reaching the next waypoint takes no time, so it happens on arrival
+    let arrive = |mut reached: usize, pos: &Point| {
+        while reached < last_leg && waypoints[reached + 1] == *pos {
+            reached += 1;
+        }
+        return reached;
+    };
+    // a trip of one waypoint can still fail if a storm sits on it
+    let unreachable = |leg: usize| TripError::Unreachable {
+        leg, from: waypoints[leg].clone(), to: waypoints.get(leg + 1).unwrap_or(start).clone(),
+    };
+
+    if !forecast.is_open(start, start_time) {
+        return Err(unreachable(0));
+    }
+    let reached = arrive(0, start);
+    let mut steps = vec![Step { time: start_time, pos: start.clone(), reached, dir: None, prev: None }];
+    let mut footprints = Footprints::new(forecast);
+    footprints.visit_reached(start_time, start, reached);
+    let mut furthest = reached;
+    let mut level: Vec<usize> = vec![0]; // steps of the same minute
+
+    while !level.is_empty() {
+        if let Some(top) = level.iter().find(|step| steps[**step].reached == last_leg) {
+            let (top, top_time) = (*top, steps[*top].time);
+            // the first step in the route that has reached each waypoint
+            let mut arrivals = vec![top_time; last_leg];
+            let mut cur = top;
+            while let Some(prev) = steps[cur].prev {
+                for arrival in arrivals.iter_mut().take(steps[prev].reached) {
+                    *arrival = steps[prev].time;
+                }
+                cur = prev;
+            }
+            let legs = arrivals.iter()
+                .scan(start_time, |left, arrival| {
+                    let minutes = arrival - *left;
+                    *left = *arrival;
+                    return Some(minutes);
+                })
+                .collect();
+            return Ok(Itinerary { start_time, legs, route: trace_route(&steps, top) });
+        }
+
+        // states that arrive at a waypoint this minute have to wait their turn
+        let mut arrivals = vec![];
+        let mut next_level = vec![];
+        for top in level {
+            let (top_time, top_reached) = (steps[top].time, steps[top].reached);
+            // a state that arrived later in the same minute has reached more
+            if !footprints.is_best(top_time, &steps[top].pos, top_reached) {
+                continue;
+            }
+            for (dir, next_cur) in forecast.get_next_curs(&steps[top].pos, top_time) {
+                let reached = arrive(top_reached, &next_cur);
+                if reached == top_reached {
+                    if footprints.visit_reached(top_time + 1, &next_cur, reached) {
+                        steps.push(Step { time: top_time + 1, pos: next_cur, reached, dir, prev: Some(top) });
+                        next_level.push(steps.len() - 1);
+                    }
+                } else {
+                    arrivals.push((top, dir, next_cur, reached));
+                }
+            }
+        }
+        for (top, dir, next_cur, reached) in arrivals {
+            let time = steps[top].time + 1;
+            if footprints.visit_reached(time, &next_cur, reached) {
+                furthest = furthest.max(reached);
+                steps.push(Step { time, pos: next_cur, reached, dir, prev: Some(top) });
+                next_level.push(steps.len() - 1);
             }
         }
+        level = next_level;
     }
 
-    panic!("ran out of time")
+    return Err(unreachable(furthest));
+}
+
+fn manhattan(a: &Point, b: &Point) -> usize {
+    return ((a.x - b.x).abs() + (a.y - b.y).abs()) as usize;
+}
+
+/// A* with the manhattan distance to the stop as the heuristic: no route
+/// can take fewer minutes than that, so the first time the stop comes off
+/// the heap is still the best time. Ties go to the state closer to the stop.
+fn astar(forecast: &Forecast, start: &Point, stop: &Point, start_time: usize) -> Result<Vec<Option<Dir>>, TripError> {
+    let unreachable = || TripError::Unreachable { leg: 0, from: start.clone(), to: stop.clone() };
+    if forecast.is_wall(start) || forecast.is_wall(stop) || !forecast.is_open(start, start_time) {
+        return Err(unreachable());
+    }
+    let mut steps = vec![Step { time: start_time, pos: start.clone(), reached: 0, dir: None, prev: None }];
+    // (minutes so far + minutes to go at least, minutes to go at least, step)
+    let mut heap: BinaryHeap<Reverse<(usize, usize, usize)>> = BinaryHeap::new();
+    heap.push(Reverse((manhattan(start, stop), manhattan(start, stop), 0)));
+    let mut footprints = Footprints::new(forecast);
+
+    while let Some(Reverse((_, _, top))) = heap.pop() {
+        let (top_time, top_cur) = (steps[top].time, steps[top].pos.clone());
+        // a state can be pushed more than once; only the first pop counts
+        if !footprints.visit(top_time, &top_cur) { continue; }
+        if top_cur == *stop { return Ok(trace_route(&steps, top)); }
+
+        for (dir, next_cur) in forecast.get_next_curs(&top_cur, top_time) {
+            let h = manhattan(&next_cur, stop);
+            steps.push(Step { time: top_time + 1, pos: next_cur, reached: 0, dir, prev: Some(top) });
+            heap.push(Reverse((top_time + 1 - start_time + h, h, steps.len() - 1)));
+        }
+    }
+
+    return Err(unreachable());
+}
+
+/// Walk the route from "start" at minute "start_time", stepping the storms
+/// along, and check that the expedition never shares a cell with a storm.
+/// With "render", show every minute like an animation. Return where the
+/// route ends.
+fn follow(map: &Map, start_time: usize, start: &Point, route: &[Option<Dir>], render: bool) -> Point {
+    let mut map = map.clone();
+    for _ in 0..start_time {
+        map.storms = map.step_storm();
+    }
+    map.cur = start.clone();
+    if render {
+        map.render();
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    for (minute, dir) in route.iter().enumerate() {
+        map.storms = map.step_storm();
+        let (x_delta, y_delta) = match dir {
+            Some(Dir::Up) => (0, -1),
+            Some(Dir::Down) => (0, 1),
+            Some(Dir::Left) => (-1, 0),
+            Some(Dir::Right) => (1, 0),
+            None => (0, 0),
+        };
+        map.cur = Point::new(map.cur.x + x_delta, map.cur.y + y_delta);
+        assert!(!map.walls.contains(&map.cur));
+        assert!(map.storms.iter().all(|storm| storm.pos != map.cur));
+        if render {
+            map.render();
+            match dir {
+                Some(dir) => println!("minute {}: move {dir:?}", start_time + minute + 1),
+                None => println!("minute {}: wait", start_time + minute + 1),
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+    return map.cur;
+}
+
+/// "entrance", "exit", or "x,y" for any other cell
+fn parse_waypoint(map: &Map, arg: &str) -> Point {
+    return match arg {
+        "entrance" => Point::new(1, 0),
+        "exit" => map.stop.clone(),
+        _ => {
+            let (x, y) = arg.split_once(',').expect("waypoints are entrance, exit, or x,y");
+            Point::new(x.trim().parse().unwrap(), y.trim().parse().unwrap())
+        }
+    };
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/24.txt").unwrap();
-    // part 1
     let map = Map::parse_inputs(&inputs);
+    let args: Vec<String> = env::args().collect();
+    let start = time::Instant::now();
+    let forecast = Forecast::new(&map);
+    println!("Forecast of {} minutes took {:.2?}", forecast.period, start.elapsed());
+    let check = args.iter().any(|arg| arg == "--check");
+    if check {
+        forecast.check(&map, 20);
+    }
+
+    // part 1, both ways; A* has to be exactly as fast as BFS
+    let start = time::Instant::now();
+    let route = plan_trip(&forecast, &[map.cur.clone(), map.stop.clone()], 0).unwrap().route;
+    println!("{:?}, {:.2?} elapsed", route.len(), start.elapsed());
     let start = time::Instant::now();
-    let (time, _) = bfs(&map, 1000);
-    println!("{time:?}, {:.2?} elapsed", start.elapsed());
+    let route_astar = astar(&forecast, &map.cur, &map.stop, 0).unwrap();
+    println!("{:?} with A*, {:.2?} elapsed", route_astar.len(), start.elapsed());
+    assert_eq!(route.len(), route_astar.len());
+    if check {
+        assert_eq!(follow(&map, 0, &map.cur, &route_astar, false), map.stop);
+    }
+    let replay = args.iter().any(|arg| arg == "--replay");
+    if check || replay {
+        assert_eq!(follow(&map, 0, &map.cur, &route, replay), map.stop);
+    }
 
+    // part 2 by default; `--trip entrance 5,3 exit` for any other trip
+    let waypoints: Vec<Point> = match args.iter().position(|arg| arg == "--trip") {
+        Some(i) => args[i + 1..].iter()
+            .take_while(|arg| !arg.starts_with("--"))
+            .map(|arg| parse_waypoint(&map, arg))
+            .collect(),
+        None => ["entrance", "exit", "entrance", "exit"].iter()
+            .map(|arg| parse_waypoint(&map, arg))
+            .collect(),
+    };
     let start = time::Instant::now();
-    let mut map = Map::parse_inputs(&inputs);
-    let (time_1, new_map) = bfs(&map, 1000);
-    println!("Trip 1 took {time_1}, {:.2?} elapsed", start.elapsed());
-    map = new_map;
-    map.stop = Point::new(1, 0);
-    let (time_2, new_map) = bfs(&map, 1000);
-    println!("Trip 2 took {time_2}, {:.2?} elapsed", start.elapsed());
-    map = new_map;
-    let (nrows, ncols) = map.shape;
-    map.stop = Point::new(ncols as i32 - 2, nrows as i32 - 1);
-    let (time_3, _) = bfs(&map, 1000);
-    println!("Trip 3 took {time_3}, {:.2?} elapsed", start.elapsed());
-    println!("{}", time_1 + time_2 + time_3);
+    match plan_trip(&forecast, &waypoints, 0) {
+        Ok(trip) => {
+            for (i, minutes) in trip.legs.iter().enumerate() {
+                println!("Leg {} took {minutes}", i + 1);
+            }
+            println!("{}, {:.2?} elapsed", trip.total(), start.elapsed());
+            if check {
+                assert_eq!(follow(&map, trip.start_time, &waypoints[0], &trip.route, false), *waypoints.last().unwrap());
+            }
+        }
+        Err(err) => println!("No trip: {err}"),
+    }
 }
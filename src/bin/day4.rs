@@ -1,35 +1,22 @@
+use aoc2022::interval::Range;
 use std::fs;
 
-struct Range {
-    start: i32,
-    stop: i32,
-}
-
-impl Range {
-    fn from_range_str(s: &str) -> Self {
-        let mut nums = s.split("-");
-        let start = nums.next().unwrap().parse::<i32>().unwrap();
-        let stop = nums.next().unwrap().parse::<i32>().unwrap();
+/// s is like "2-4"
+fn parse_range(s: &str) -> Range<i32> {
+    let mut nums = s.split("-");
+    let start = nums.next().unwrap().parse::<i32>().unwrap();
+    let stop = nums.next().unwrap().parse::<i32>().unwrap();
 
-        return Self { start, stop };
-    }
-    /// line is like "2-4,6-8"
-    fn from_line(line: &str) -> (Self, Self) {
-        let mut ranges = line.split(",");
-        let range1 = Range::from_range_str(ranges.next().unwrap());
-        let range2 = Range::from_range_str(ranges.next().unwrap());
-
-        return (range1, range2);
-    }
+    return Range::new(start, stop);
+}
 
-    fn contains(&self, other: &Self) -> bool {
-        return (self.start >= other.start && self.stop <= other.stop)
-            || (self.start <= other.start && self.stop >= other.stop);
-    }
+/// line is like "2-4,6-8"
+fn parse_line(line: &str) -> (Range<i32>, Range<i32>) {
+    let mut ranges = line.split(",");
+    let range1 = parse_range(ranges.next().unwrap());
+    let range2 = parse_range(ranges.next().unwrap());
 
-    fn overlaps(&self, other: &Self) -> bool {
-        return !((self.stop < other.start) || (self.start > other.stop));
-    }
+    return (range1, range2);
 }
 
 fn main() {
@@ -37,7 +24,7 @@ fn main() {
     let sum = input
         .lines()
         .map(|line| {
-            let (r1, r2) = Range::from_line(line);
+            let (r1, r2) = parse_line(line);
             if r1.contains(&r2) || r2.contains(&r1) {
                 return 1;
             }
@@ -48,8 +35,8 @@ fn main() {
     let sum = input
         .lines()
         .map(|line| {
-            let (r1, r2) = Range::from_line(line);
-            if r1.overlaps(&r2) || r2.overlaps(&r1) {
+            let (r1, r2) = parse_line(line);
+            if r1.overlaps(&r2) {
                 return 1;
             }
             return 0;
@@ -1,31 +1,70 @@
+//! Day 7: No Space Left On Device
+//! The transcript is replayed into a small virtual filesystem, which can then
+//! be queried like a shell:
+//! `cargo run --bin day7 -- du <path>`, `-- find --min-size <bytes>`, or
+//! `-- free <bytes>` for the smallest directory of at least that many bytes.
+//! Relative paths start from where the transcript left off.
+//!
+//! `-- tree [--sort name|size]` prints the rebuilt filesystem like `tree`
+//! does, with sizes, and `-- json [--sort name|size]` exports it as nested
+//! JSON.
+//!
+//! With `--check`, directory sizes are also computed a second way, streaming
+//! through the transcript with a stack of the directories that are open, and
+//! both ways have to agree.
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
 use std::rc::Rc;
 
+const TOTAL_SPACE: u64 = 70000000;
+const NEEDED_SPACE: u64 = 30000000;
+
 #[derive(Debug)]
 enum Item {
     Dir(String, String, Vec<Rc<RefCell<Item>>>), // path, name, items
     File(String, u64),                           // filename, size
 }
 
+/// Order of the items in a dir when printing
+#[derive(Clone, Copy)]
+enum SortBy {
+    Name,
+    Size, // largest first, then by name
+}
+
 impl Item {
-    fn _to_string(&self) -> String {
+    /// One line of the tree: dirs end with "/", and every item has its size
+    fn label(&self) -> String {
         match self {
-            Self::File(name, size) => format!("<File {} {}>", name, size),
-            Self::Dir(path, name, items) => {
-                format!(
-                    "<Dir {} {} nitems={} size={}>",
-                    path,
-                    name,
-                    items.len(),
-                    self.size()
-                )
-            }
+            Self::File(name, size) => format!("{} ({})", name, size),
+            Self::Dir(path, _, _) if path == "/" => format!("/ ({})", self.size()),
+            Self::Dir(_, name, _) => format!("{}/ ({})", name, self.size()),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::File(name, _) => return name,
+            Self::Dir(_, name, _) => return name,
         }
     }
 
-    /// Push a file onto dir.items
+    /// The item in this dir with the given name
+    fn child(&self, name: &str) -> Option<Rc<RefCell<Item>>> {
+        if let Self::Dir(_, _, items) = self {
+            return items.iter().find(|item| item.borrow().name() == name).cloned();
+        }
+        return None;
+    }
+
+    /// Push a file onto dir.items, unless it is already there, which happens
+    /// when a dir is listed more than once
     fn push_file(&mut self, filename: &str, size: u64) {
+        if self.child(filename).is_some() {
+            return;
+        }
         if let Self::Dir(_, _, items) = self {
             items.push(Rc::new(RefCell::new(Item::File(
                 filename.to_string(),
@@ -34,33 +73,21 @@ impl Item {
         }
     }
 
-    fn push_dir(&mut self, dirname: &str) {
+    /// Push a dir onto dir.items unless it is already there, and return it
+    fn push_dir(&mut self, dirname: &str) -> Rc<RefCell<Item>> {
+        if let Some(item) = self.child(dirname) {
+            return item;
+        }
         if let Self::Dir(path, _, items) = self {
-            let mut path = path.clone();
-            if path != "/" {
-                path.push_str("/");
-            }
-            path.push_str(dirname);
-            items.push(Rc::new(RefCell::new(Item::Dir(
-                path.to_string(),
+            let dir = Rc::new(RefCell::new(Item::Dir(
+                join_path(path, dirname),
                 dirname.to_string(),
                 vec![],
-            ))));
+            )));
+            items.push(Rc::clone(&dir));
+            return dir;
         }
-    }
-
-    /// Takes a cursor, then return a cursor that points to the target dir
-    fn chdir(&self, dirname: &str) -> Rc<RefCell<Item>> {
-        if let Self::Dir(_, _, items) = self {
-            for item in items {
-                if let Item::Dir(_, n, _) = &*item.borrow() {
-                    if n == dirname {
-                        return Rc::clone(item);
-                    }
-                }
-            }
-        }
-        unreachable!();
+        unreachable!("only dirs have items");
     }
 
     fn size(&self) -> u64 {
@@ -71,6 +98,93 @@ impl Item {
             }
         }
     }
+
+    /// The items of a dir in the given order; nothing for a file
+    fn sorted_items(&self, sort: SortBy) -> Vec<Rc<RefCell<Item>>> {
+        let mut items = match self {
+            Self::File(..) => vec![],
+            Self::Dir(_, _, items) => items.clone(),
+        };
+        match sort {
+            SortBy::Name => items.sort_by(|a, b| a.borrow().name().cmp(b.borrow().name())),
+            SortBy::Size => items.sort_by(|a, b| {
+                let (a, b) = (a.borrow(), b.borrow());
+                return b.size().cmp(&a.size()).then(a.name().cmp(b.name()));
+            }),
+        }
+        return items;
+    }
+
+    /// Like the `tree` command, with sizes:
+    ///
+    /// / (48381165)
+    /// ├── a/ (94853)
+    /// │   └── e/ (584)
+    /// ...
+    fn render_tree(&self, sort: SortBy) -> String {
+        let mut lines = vec![self.label()];
+        self.render_children(sort, "", &mut lines);
+        return lines.join("\n") + "\n";
+    }
+
+    fn render_children(&self, sort: SortBy, prefix: &str, lines: &mut Vec<String>) {
+        let items = self.sorted_items(sort);
+        for (i, item) in items.iter().enumerate() {
+            let is_last = i == items.len() - 1;
+            let (branch, indent) = match is_last {
+                true => ("└── ", "    "),
+                false => ("├── ", "│   "),
+            };
+            lines.push(format!("{prefix}{branch}{}", item.borrow().label()));
+            item.borrow().render_children(sort, &format!("{prefix}{indent}"), lines);
+        }
+    }
+
+    /// Nested JSON: a dir is {"name", "type": "dir", "size", "items"} and a
+    /// file is {"name", "type": "file", "size"}
+    fn to_json(&self, sort: SortBy) -> String {
+        match self {
+            Self::File(name, size) => {
+                return format!("{{\"name\":{},\"type\":\"file\",\"size\":{size}}}", json_string(name));
+            }
+            Self::Dir(path, name, _) => {
+                let name = if path == "/" { "/" } else { name };
+                let items: Vec<String> = self
+                    .sorted_items(sort)
+                    .iter()
+                    .map(|item| item.borrow().to_json(sort))
+                    .collect();
+                return format!(
+                    "{{\"name\":{},\"type\":\"dir\",\"size\":{},\"items\":[{}]}}",
+                    json_string(name),
+                    self.size(),
+                    items.join(",")
+                );
+            }
+        }
+    }
+}
+
+/// A JSON string literal
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path == "/" {
+        return format!("/{name}");
+    }
+    return format!("{path}/{name}");
 }
 
 /// Recusively walk through all items and collect them into a single vector
@@ -105,92 +219,258 @@ fn parse_cd(cd: &str) -> String {
 fn parse_ls(ls: &str) -> (Vec<String>, Vec<(String, u64)>) {
     let mut dirs = vec![];
     let mut files = vec![];
-    ls.lines().for_each(|line| {
-        if line.contains("dir") {
-            let mut tokens = line.split(" ");
-            tokens.next(); // "dir"
-            dirs.push(tokens.next().unwrap().to_string());
-        } else if &line[..2] == "ls" {
-            // do nothing
+    ls.lines().skip(1).for_each(|line| {
+        let (first, name) = line.split_once(" ").unwrap();
+        if first == "dir" {
+            dirs.push(name.to_string());
         } else {
-            let mut tokens = line.split(" ");
-            let size = tokens.next().unwrap().parse::<u64>().unwrap();
-            let filename = tokens.next().unwrap().to_string();
-            files.push((filename, size));
+            files.push((name.to_string(), first.parse::<u64>().unwrap()));
         }
     });
 
     return (dirs, files);
 }
 
-fn main() {
-    // Parsing command and mutating the state
-    let input = fs::read_to_string("inputs/7.txt").unwrap();
+/// Apply a cd argument to the working directory, given as a list of names
+/// from the root. The argument can be absolute or relative, "." stays, and
+/// ".." at the root stays at the root like a real shell.
+fn change_dir(cwd: &mut Vec<String>, dest: &str) {
+    if dest.starts_with('/') {
+        cwd.clear();
+    }
+    for part in dest.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => {
+                cwd.pop();
+            }
+            name => cwd.push(name.to_string()),
+        }
+    }
+}
+
+fn to_path(cwd: &[String]) -> String {
+    return cwd.iter().fold("/".to_string(), |path, name| join_path(&path, name));
+}
 
-    let root = Rc::new(RefCell::new(Item::Dir(
-        "/".to_string(),
-        "".to_string(),
-        vec![],
-    )));
-    let mut cur = Rc::clone(&root);
-    let mut stack: Vec<Rc<RefCell<Item>>> = vec![]; // push and pop
-
-    input.split("$ ").for_each(|cmd| {
-        if cmd.len() < 2 {
-            ()
-        } else if &cmd[0..2] == "ls" {
+/// The filesystem as rebuilt from a transcript
+struct Vfs {
+    root: Rc<RefCell<Item>>,
+    cwd: Vec<String>, // names from the root
+}
+
+impl Vfs {
+    fn new() -> Self {
+        let root = Rc::new(RefCell::new(Item::Dir(
+            "/".to_string(),
+            "".to_string(),
+            vec![],
+        )));
+        return Self { root, cwd: vec![] };
+    }
+
+    /// Replay every command of a transcript
+    fn from_transcript(transcript: &str) -> Self {
+        let mut vfs = Self::new();
+        transcript.split("$ ").for_each(|cmd| {
+            if cmd.starts_with("ls") {
+                let (dirs, files) = parse_ls(cmd);
+                let cur = vfs.lookup(&vfs.cwd.clone());
+                dirs.iter().for_each(|dirname| {
+                    cur.borrow_mut().push_dir(dirname);
+                });
+                files
+                    .iter()
+                    .for_each(|(filename, size)| cur.borrow_mut().push_file(filename, *size));
+            } else if cmd.starts_with("cd") {
+                change_dir(&mut vfs.cwd, &parse_cd(cmd));
+            }
+        });
+        return vfs;
+    }
+
+    /// The dir at the given names from the root. A dir that has not been
+    /// listed yet is created, since the transcript went into it.
+    fn lookup(&self, names: &[String]) -> Rc<RefCell<Item>> {
+        let mut cur = Rc::clone(&self.root);
+        for name in names {
+            let next = cur.borrow_mut().push_dir(name);
+            cur = next;
+        }
+        return cur;
+    }
+
+    /// The item at an absolute path, or a path relative to the working dir
+    fn resolve(&self, path: &str) -> Option<Rc<RefCell<Item>>> {
+        let mut names = self.cwd.clone();
+        change_dir(&mut names, path);
+        let mut cur = Rc::clone(&self.root);
+        for name in &names {
+            let next = cur.borrow().child(name)?;
+            cur = next;
+        }
+        return Some(cur);
+    }
+
+    /// Total size of everything under the path
+    fn du(&self, path: &str) -> Option<u64> {
+        return self.resolve(path).map(|item| item.borrow().size());
+    }
+
+    /// Every dir and its size
+    fn dir_sizes(&self) -> Vec<(String, u64)> {
+        return flatten(Rc::clone(&self.root))
+            .iter()
+            .filter_map(|item| match &*item.borrow() {
+                Item::Dir(path, ..) => Some((path.clone(), item.borrow().size())),
+                Item::File(..) => None,
+            })
+            .collect();
+    }
+
+    /// Dirs whose size is within the limits, both inclusive
+    fn find_dirs(&self, min_size: u64, max_size: u64) -> Vec<(String, u64)> {
+        return self
+            .dir_sizes()
+            .into_iter()
+            .filter(|(_, size)| (min_size..=max_size).contains(size))
+            .collect();
+    }
+
+    /// The smallest dir of at least the given size; None if no dir is that big
+    fn smallest_dir_freeing(&self, bytes: u64) -> Option<(String, u64)> {
+        return self.find_dirs(bytes, u64::MAX).into_iter().min_by_key(|(_, size)| *size);
+    }
+
+    /// How much has to be deleted to have "needed" bytes available on a disk
+    /// of "total" bytes; 0 if that much is available already
+    fn space_to_free(&self, needed: u64, total: u64) -> u64 {
+        return (self.root.borrow().size() + needed).saturating_sub(total);
+    }
+}
+
+/// Move the stack to the working dir: leave dirs up to the common ancestor,
+/// adding their totals to their parents and to their sizes, then enter the
+/// rest. stack[depth] is the dir at cwd[..depth].
+fn sync_stack(stack: &mut Vec<(String, u64)>, cwd: &[String], sizes: &mut HashMap<String, u64>) {
+    let common = stack
+        .iter()
+        .zip(0..=cwd.len())
+        .take_while(|((path, _), depth)| *path == to_path(&cwd[..*depth]))
+        .count();
+    while stack.len() > common {
+        let (path, size) = stack.pop().unwrap();
+        // a dir that was never listed has nothing in it to add
+        if let Some(total) = sizes.get_mut(&path) {
+            *total += size;
+        }
+        if let Some((_, parent)) = stack.last_mut() {
+            *parent += size;
+        }
+    }
+    for depth in stack.len()..=cwd.len() {
+        stack.push((to_path(&cwd[..depth]), 0));
+    }
+}
+
+/// Dir sizes without building a tree: the stack holds the running total of
+/// every dir between the root and the working dir. Leaving a dir adds its
+/// total to the one above it, so everything ends up in the root once the
+/// stack is emptied at the end. A dir that is entered again later just adds
+/// to what it had, and a file that is listed again is skipped. Like in the
+/// filesystem, a dir only exists once it is listed, listed into, or is above
+/// a dir that is listed; just going into it with `cd` does not count.
+fn dir_sizes_by_stack(transcript: &str) -> HashMap<String, u64> {
+    let mut sizes: HashMap<String, u64> = HashMap::from([("/".to_string(), 0)]);
+    let mut seen_files: HashSet<String> = HashSet::new(); // paths
+    let mut stack: Vec<(String, u64)> = vec![];
+    let mut cwd: Vec<String> = vec![];
+
+    sync_stack(&mut stack, &cwd, &mut sizes);
+    transcript.split("$ ").for_each(|cmd| {
+        if cmd.starts_with("cd") {
+            change_dir(&mut cwd, &parse_cd(cmd));
+            sync_stack(&mut stack, &cwd, &mut sizes);
+        } else if cmd.starts_with("ls") {
+            for (path, _) in &stack {
+                sizes.entry(path.clone()).or_insert(0);
+            }
+            let (path, total) = stack.last_mut().unwrap();
             let (dirs, files) = parse_ls(cmd);
-            dirs.iter()
-                .for_each(|dirname| cur.borrow_mut().push_dir(dirname));
-            files
-                .iter()
-                .for_each(|(filename, size)| cur.borrow_mut().push_file(filename, *size));
-        } else if &cmd[..2] == "cd" {
-            let dest = parse_cd(cmd);
-            match &dest[..] {
-                "/" => {
-                    cur = Rc::clone(&root);
-                    while stack.len() > 0 {
-                        stack.pop();
-                    }
-                }
-                ".." => {
-                    cur = stack.pop().unwrap();
-                }
-                _ => {
-                    stack.push(Rc::clone(&cur));
-                    let next = Rc::clone(&cur.borrow_mut().chdir(&dest));
-                    cur = Rc::clone(&next);
+            for dirname in dirs {
+                sizes.entry(join_path(path, &dirname)).or_insert(0);
+            }
+            for (filename, size) in files {
+                if seen_files.insert(join_path(path, &filename)) {
+                    *total += size;
                 }
             }
         }
     });
+    cwd.clear();
+    sync_stack(&mut stack, &cwd, &mut sizes);
+    let (path, size) = stack.pop().unwrap();
+    *sizes.entry(path).or_insert(0) += size;
 
-    let items = flatten(Rc::clone(&root));
-    let total_space = 70000000;
-    let needed_space = 30000000;
-    let available_space = total_space - root.borrow().size();
-    let threshold = needed_space - available_space;
-    let mut sum = 0;
-    let mut min = total_space;
-
-    for item in items {
-        let item_ = &*item.borrow();
-        match item_ {
-            Item::Dir(..) => {
-                // println!("{path}, {}", item_.size());
-                if item_.size() <= 100000 {
-                    sum += item_.size();
-                }
-                if item_.size() >= threshold && item_.size() < min {
-                    min = item_.size();
-                }
+    return sizes;
+}
+
+fn main() {
+    // Parsing command and mutating the state
+    let input = fs::read_to_string("inputs/7.txt").unwrap();
+    let vfs = Vfs::from_transcript(&input);
+
+    if env::args().any(|arg| arg == "--check") {
+        let by_stack = dir_sizes_by_stack(&input);
+        assert_eq!(vfs.dir_sizes().into_iter().collect::<HashMap<String, u64>>(), by_stack);
+    }
+
+    let args: Vec<String> = env::args().filter(|arg| arg != "--check").collect();
+    let arg_at = |i: usize| args.get(i).map(|arg| arg.as_str());
+    let sort = match args.iter().position(|arg| arg == "--sort").and_then(|i| arg_at(i + 1)) {
+        Some("size") => SortBy::Size,
+        Some("name") | None => SortBy::Name,
+        Some(other) => panic!("cannot sort by {other}"),
+    };
+    match (arg_at(1), arg_at(2), arg_at(3)) {
+        (Some("tree"), ..) => {
+            print!("{}", vfs.root.borrow().render_tree(sort));
+            return;
+        }
+        (Some("json"), ..) => {
+            println!("{}", vfs.root.borrow().to_json(sort));
+            return;
+        }
+        (Some("du"), path, _) => {
+            match vfs.du(path.unwrap_or("/")) {
+                Some(size) => println!("{size}"),
+                None => println!("no such file or directory"),
             }
-            Item::File(..) => {
-                // println!("{name} {size}");
+            return;
+        }
+        (Some("find"), Some("--min-size"), Some(min_size)) => {
+            for (path, size) in vfs.find_dirs(min_size.parse().unwrap(), u64::MAX) {
+                println!("{size}\t{path}");
+            }
+            return;
+        }
+        (Some("free"), Some(bytes), _) => {
+            match vfs.smallest_dir_freeing(bytes.parse().unwrap()) {
+                Some((path, size)) => println!("{size}\t{path}"),
+                None => println!("no directory is that big"),
             }
+            return;
         }
+        _ => (),
     }
+
+    let sum: u64 = vfs.find_dirs(0, 100000).iter().map(|(_, size)| size).sum();
     println!("{sum}");
-    println!("{min}");
+    match vfs.space_to_free(NEEDED_SPACE, TOTAL_SPACE) {
+        0 => println!("nothing to delete"),
+        to_free => {
+            let (_, min) = vfs.smallest_dir_freeing(to_free).unwrap();
+            println!("{min}");
+        }
+    }
 }
@@ -1,7 +1,16 @@
+//! Day 9: Rope Bridge
+//! Besides R, U, L, and D, the head can move diagonally (UR, UL, DR, DL), or
+//! by any offset at once with "M <dx> <dy>"; it then goes one cell at a time,
+//! diagonally first, so the knots can keep up.
+//!
+//! `cargo run --bin day9 -- --ascii <knots>` prints the cells visited by the
+//! tail of a rope with that many knots, `--svg <knots>` draws its trail.
+use std::collections::HashSet;
+use std::env;
 use std::fs;
 use std::str::Lines;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 struct Point {
     x: i64,
     y: i64,
@@ -15,8 +24,9 @@ impl Point {
 
 #[derive(Debug)]
 struct Simulation {
-    knots: Vec<Point>, // has at least 2 elements; knots[i] follows knots[i-1]
-    trail: Vec<Point>,
+    knots: Vec<Point>,          // has at least 2 elements; knots[i] follows knots[i-1]
+    visits: Vec<HashSet<Point>>, // cells visited by each knot
+    trail: Vec<Point>,          // cells visited by the tail, in order
 }
 
 impl Simulation {
@@ -26,14 +36,16 @@ impl Simulation {
         for _ in 0..n {
             knots.push(Point { x: 0, y: 0 });
         }
+        let start = HashSet::from([Point { x: 0, y: 0 }]);
         return Self {
             knots,
-            trail: vec![],
+            visits: vec![start; n],
+            trail: vec![Point { x: 0, y: 0 }],
         };
     }
 
     fn add_trail(&mut self, point: &Point) {
-        if !self.trail.contains(point) {
+        if self.trail.last() != Some(point) {
             self.trail.push(point.clone());
         }
     }
@@ -76,43 +88,147 @@ impl Simulation {
             Self::update_tail(&head, tail);
         }
 
+        for (knot, visits) in self.knots.iter().zip(self.visits.iter_mut()) {
+            visits.insert(knot.clone());
+        }
         let tail = self.knots.get(self.knots.len() - 1).unwrap().clone();
         self.add_trail(&tail);
     }
 
-    fn move_head(&mut self, (xdelta, ydelta): (i64, i64)) {
-        let head = self.knots.get_mut(0).unwrap();
-        head.x += xdelta;
-        head.y += ydelta;
-        self.update_knots();
+    /// Move the head by any offset, one cell at a time so that no knot ever
+    /// falls more than one cell behind the one it follows
+    fn move_head(&mut self, (mut xdelta, mut ydelta): (i64, i64)) {
+        while (xdelta, ydelta) != (0, 0) {
+            let (xstep, ystep) = (xdelta.signum(), ydelta.signum());
+            let head = self.knots.get_mut(0).unwrap();
+            head.x += xstep;
+            head.y += ystep;
+            self.update_knots();
+            xdelta -= xstep;
+            ydelta -= ystep;
+        }
     }
-}
 
-fn simulate_rope(n: usize, cmds: Lines) {
-    let mut sim = Simulation::new(n);
-    cmds.for_each(|cmd| {
-        let mut tokens = cmd.split(" ");
-        let direction = tokens.next().unwrap();
-        let count = tokens.next().unwrap();
-        let count = count.parse::<usize>().unwrap();
-
-        for _ in 0..count {
-            match direction {
-                "R" => sim.move_head((1, 0)),
-                "U" => sim.move_head((0, 1)),
-                "L" => sim.move_head((-1, 0)),
-                "D" => sim.move_head((0, -1)),
-                _ => unreachable!("Illegal direction!"),
+    /// Number of cells visited by each knot, head first
+    fn count_visits(&self) -> Vec<usize> {
+        return self.visits.iter().map(|visits| visits.len()).collect();
+    }
+
+    /// min x, max x, min y, max y of the cells visited by the tail
+    fn get_bounds(&self) -> (i64, i64, i64, i64) {
+        let xs = self.trail.iter().map(|p| p.x);
+        let ys = self.trail.iter().map(|p| p.y);
+        return (xs.clone().min().unwrap(), xs.max().unwrap(), ys.clone().min().unwrap(), ys.max().unwrap());
+    }
+
+    /// The cells visited by the tail like the puzzle draws them: "#" for
+    /// visited, "s" for the start, up is up
+    fn render_ascii(&self) -> String {
+        let tail_visits = self.visits.last().unwrap();
+        let (x_min, x_max, y_min, y_max) = self.get_bounds();
+        let mut grid = String::new();
+        for y in (y_min..=y_max).rev() {
+            for x in x_min..=x_max {
+                let p = Point { x, y };
+                if p == (Point { x: 0, y: 0 }) {
+                    grid.push('s');
+                } else if tail_visits.contains(&p) {
+                    grid.push('#');
+                } else {
+                    grid.push('.');
+                }
             }
+            grid.push('\n');
         }
-    });
+        return grid;
+    }
 
-    println!("{}", sim.trail.len());
+    /// The visited cells as squares and the trail of the tail as a line
+    /// through them, in order
+    fn render_svg(&self) -> String {
+        let scale = 4;
+        let (x_min, x_max, y_min, y_max) = self.get_bounds();
+        let (width, height) = ((x_max - x_min + 1) * scale, (y_max - y_min + 1) * scale);
+        // svg's y goes down
+        let to_svg = |p: &Point| ((p.x - x_min) * scale, (y_max - p.y) * scale);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+        );
+        for p in self.visits.last().unwrap() {
+            let (x, y) = to_svg(p);
+            svg.push_str(&format!("<rect x=\"{x}\" y=\"{y}\" width=\"{scale}\" height=\"{scale}\" fill=\"#ccc\"/>\n"));
+        }
+        let points: Vec<String> = self
+            .trail
+            .iter()
+            .map(|p| {
+                let (x, y) = to_svg(p);
+                return format!("{},{}", x + scale / 2, y + scale / 2);
+            })
+            .collect();
+        svg.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"#c00\" stroke-width=\"1\"/>\n",
+            points.join(" ")
+        ));
+        svg.push_str("</svg>\n");
+        return svg;
+    }
+}
+
+/// The offset a command moves the head by: a direction and a number of
+/// cells, or "M" and any offset
+fn parse_cmd(cmd: &str) -> (i64, i64) {
+    let tokens: Vec<&str> = cmd.split(" ").collect();
+    if tokens[0] == "M" {
+        return (tokens[1].parse().unwrap(), tokens[2].parse().unwrap());
+    }
+    let count = tokens[1].parse::<i64>().unwrap();
+    let (xdelta, ydelta) = match tokens[0] {
+        "R" => (1, 0),
+        "U" => (0, 1),
+        "L" => (-1, 0),
+        "D" => (0, -1),
+        "UR" => (1, 1),
+        "UL" => (-1, 1),
+        "DR" => (1, -1),
+        "DL" => (-1, -1),
+        _ => unreachable!("Illegal direction!"),
+    };
+    return (xdelta * count, ydelta * count);
+}
+
+fn simulate_rope(n: usize, cmds: Lines) -> Simulation {
+    let mut sim = Simulation::new(n);
+    cmds.for_each(|cmd| sim.move_head(parse_cmd(cmd)));
+    return sim;
 }
 
 fn main() {
     let inputs = fs::read_to_string("inputs/9.txt").unwrap();
+    let args: Vec<String> = env::args().collect();
+    let knots_arg = |flag: &str| {
+        let i = args.iter().position(|arg| arg == flag)?;
+        return Some(args.get(i + 1).map_or(10, |n| n.parse::<usize>().unwrap()));
+    };
+    if let Some(n) = knots_arg("--ascii") {
+        print!("{}", simulate_rope(n, inputs.lines()).render_ascii());
+        return;
+    }
+    if let Some(n) = knots_arg("--svg") {
+        print!("{}", simulate_rope(n, inputs.lines()).render_svg());
+        return;
+    }
+
+    let short = simulate_rope(2, inputs.lines());
+    let long = simulate_rope(10, inputs.lines());
+    println!("{}", short.count_visits().last().unwrap());
+    println!("{}", long.count_visits().last().unwrap());
 
-    simulate_rope(2, inputs.lines());
-    simulate_rope(10, inputs.lines());
+    // a knot only follows the knots in front of it, so the first two knots
+    // move the same way however long the rope is
+    assert_eq!(short.visits[1], long.visits[1]);
+    println!("cells visited per knot, head first");
+    println!("2 knots:  {:?}", short.count_visits());
+    println!("10 knots: {:?}", long.count_visits());
 }
@@ -0,0 +1,130 @@
+//! Cycle detection for simulations that eventually repeat themselves
+//! Day 17 asks for the height of the tower after a trillion rocks; it is only
+//! feasible because the falling rocks settle into a loop. The detector
+//! remembers a key for the state before every step; once a key comes back,
+//! the steps between the two sightings repeat forever, and a metric that
+//! grows by the same amount every lap can be worked out for any step count.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A quantity measured on the state after some number of steps. It only has
+/// to know how to carry on the growth it showed over one lap of the cycle.
+pub trait Metric: Clone {
+    /// self + laps * (after - before)
+    fn extend(&self, before: &Self, after: &Self, laps: usize) -> Self;
+}
+
+macro_rules! impl_metric {
+    ($($t:ty),*) => {
+        $(
+            impl Metric for $t {
+                fn extend(&self, before: &Self, after: &Self, laps: usize) -> Self {
+                    // wrapping so that a metric can go down without underflowing
+                    let delta = after.wrapping_sub(*before);
+                    return self.wrapping_add(delta.wrapping_mul(laps as $t));
+                }
+            }
+        )*
+    };
+}
+
+impl_metric!(i32, i64, i128, usize, u64);
+
+/// Several metrics measured at once, e.g. a counter per monkey
+impl<T: Metric> Metric for Vec<T> {
+    fn extend(&self, before: &Self, after: &Self, laps: usize) -> Self {
+        return self
+            .iter()
+            .zip(before.iter().zip(after.iter()))
+            .map(|(x, (b, a))| x.extend(b, a, laps))
+            .collect();
+    }
+}
+
+/// The state before step "start" is the same as the state before step
+/// (start + length), and so on forever
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+pub struct CycleDetector<'a, S, K, M> {
+    key: Box<dyn Fn(&S) -> K + 'a>,
+    metric: Box<dyn Fn(&S) -> M + 'a>,
+    seen: HashMap<K, usize>, // key -> step at which it was first seen
+    metrics: Vec<M>,         // metrics[i] is measured before step i
+    cycle: Option<Cycle>,
+}
+
+impl<'a, S, K: Hash + Eq, M: Metric> CycleDetector<'a, S, K, M> {
+    /// "key" has to capture everything that decides how the simulation goes
+    /// from here, otherwise two states that only look alike will be mistaken
+    /// for a cycle
+    pub fn new(key: impl Fn(&S) -> K + 'a, metric: impl Fn(&S) -> M + 'a) -> Self {
+        return Self {
+            key: Box::new(key),
+            metric: Box::new(metric),
+            seen: HashMap::new(),
+            metrics: vec![],
+            cycle: None,
+        };
+    }
+
+    pub fn cycle(&self) -> Option<Cycle> {
+        return self.cycle;
+    }
+
+    /// Number of states observed so far
+    pub fn steps(&self) -> usize {
+        return self.metrics.len();
+    }
+
+    /// Record the state before the next step; return the cycle as soon as
+    /// the state has been seen before
+    pub fn observe(&mut self, state: &S) -> Option<Cycle> {
+        if self.cycle.is_some() {
+            return self.cycle;
+        }
+        let step = self.metrics.len();
+        self.metrics.push((self.metric)(state));
+        let key = (self.key)(state);
+        if let Some(start) = self.seen.get(&key) {
+            self.cycle = Some(Cycle {
+                start: *start,
+                length: step - start,
+            });
+        } else {
+            self.seen.insert(key, step);
+        }
+        return self.cycle;
+    }
+
+    /// The metric before step n, i.e. after n steps. Steps that were observed
+    /// are looked up; later steps are worked out from the cycle, and are None
+    /// if no cycle has been found yet.
+    pub fn extrapolate(&self, n: usize) -> Option<M> {
+        if n < self.metrics.len() {
+            return Some(self.metrics[n].clone());
+        }
+        let cycle = self.cycle?;
+        let laps = (n - cycle.start) / cycle.length;
+        let offset = (n - cycle.start) % cycle.length;
+        let before = &self.metrics[cycle.start];
+        let after = &self.metrics[cycle.start + cycle.length];
+        return Some(self.metrics[cycle.start + offset].extend(before, after, laps));
+    }
+
+    /// Drive the simulation for n steps, stopping early as soon as a cycle
+    /// shows up, and return the metric after n steps
+    pub fn run(&mut self, state: &mut S, n: usize, mut advance: impl FnMut(&mut S)) -> M {
+        loop {
+            let step = self.metrics.len();
+            if self.observe(state).is_some() || step >= n {
+                break;
+            }
+            advance(state);
+        }
+        return self.extrapolate(n).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+//! Inclusive integer ranges and sets of them
+//! Day 4 compares pairs of section ranges; day 15 merges the ranges that the
+//! sensors cover on a row and looks for the gaps between them.
+use std::ops::{Add, Sub};
+
+/// The integer types that ranges can be made of. "From<u8>" is only there to
+/// get a hold of the number one.
+pub trait Integer: Copy + Ord + Add<Output = Self> + Sub<Output = Self> + From<u8> {}
+
+impl<T> Integer for T where T: Copy + Ord + Add<Output = T> + Sub<Output = T> + From<u8> {}
+
+fn one<T: Integer>() -> T {
+    return T::from(1);
+}
+
+/// Inclusive on both ends
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct Range<T> {
+    pub start: T,
+    pub stop: T,
+}
+
+impl<T: Integer> Range<T> {
+    pub fn new(start: T, stop: T) -> Self {
+        return Self { start, stop };
+    }
+
+    /// Number of integers in the range
+    pub fn len(&self) -> T {
+        return self.stop - self.start + one();
+    }
+
+    pub fn contains_point(&self, x: T) -> bool {
+        return self.start <= x && x <= self.stop;
+    }
+
+    /// Every point of other is also in self
+    pub fn contains(&self, other: &Self) -> bool {
+        return self.start <= other.start && other.stop <= self.stop;
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        return self.start <= other.stop && other.start <= self.stop;
+    }
+
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        if !self.overlaps(other) {
+            return None;
+        }
+        let start = self.start.max(other.start);
+        let stop = self.stop.min(other.stop);
+        return Some(Self::new(start, stop));
+    }
+
+    /// Overlapping or right next to each other, so that the union is still a
+    /// single range
+    pub fn can_union(&self, other: &Self) -> bool {
+        return other.start <= self.stop + one() && self.start <= other.stop + one();
+    }
+
+    /// Assume that the two actually overlaps
+    pub fn union(&self, other: &Self) -> Self {
+        let start = self.start.min(other.start);
+        let stop = self.stop.max(other.stop);
+        return Self::new(start, stop);
+    }
+}
+
+/// A set of integers stored as ranges that are sorted, disjoint, and never
+/// adjacent (adjacent ranges are merged), so every set has exactly one
+/// representation.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct IntervalSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T: Integer> IntervalSet<T> {
+    pub fn new() -> Self {
+        return Self { ranges: vec![] };
+    }
+
+    pub fn from_ranges(ranges: impl IntoIterator<Item = Range<T>>) -> Self {
+        let mut set = Self::new();
+        ranges.into_iter().for_each(|range| set.insert(range));
+        return set;
+    }
+
+    pub fn ranges(&self) -> &[Range<T>] {
+        return &self.ranges;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.ranges.is_empty();
+    }
+
+    /// Number of integers that fall within any of the ranges
+    pub fn len(&self) -> T {
+        return self
+            .ranges
+            .iter()
+            .fold(T::from(0), |total, range| total + range.len());
+    }
+
+    pub fn contains(&self, x: T) -> bool {
+        let i = self.ranges.partition_point(|range| range.stop < x);
+        return i < self.ranges.len() && self.ranges[i].contains_point(x);
+    }
+
+    /// Add the range, merging it with every range it overlaps or touches
+    pub fn insert(&mut self, new: Range<T>) {
+        // ranges[lo..hi] are the ones that can be merged with the new range
+        let lo = self.ranges.partition_point(|range| range.stop + one() < new.start);
+        let hi = self.ranges.partition_point(|range| range.start <= new.stop + one());
+        let merged = self.ranges[lo..hi]
+            .iter()
+            .fold(new, |merged, range| merged.union(range));
+        self.ranges.splice(lo..hi, [merged]);
+    }
+
+    /// Take the range out of the set, splitting the ranges it cuts through
+    pub fn remove(&mut self, cut: Range<T>) {
+        let lo = self.ranges.partition_point(|range| range.stop < cut.start);
+        let hi = self.ranges.partition_point(|range| range.start <= cut.stop);
+        if lo >= hi {
+            return;
+        }
+        let mut leftovers = vec![];
+        let (first, last) = (self.ranges[lo], self.ranges[hi - 1]);
+        if first.start < cut.start {
+            leftovers.push(Range::new(first.start, cut.start - one()));
+        }
+        if last.stop > cut.stop {
+            leftovers.push(Range::new(cut.stop + one(), last.stop));
+        }
+        self.ranges.splice(lo..hi, leftovers);
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut set = self.clone();
+        other.ranges.iter().for_each(|range| set.insert(*range));
+        return set;
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        // both lists are sorted, so walk them side by side
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            if let Some(intersection) = a.intersect(b) {
+                ranges.push(intersection);
+            }
+            if a.stop < b.stop {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        return Self { ranges };
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut set = self.clone();
+        other.ranges.iter().for_each(|range| set.remove(*range));
+        return set;
+    }
+
+    /// The ranges of integers that lie between two consecutive ranges
+    pub fn gaps(&self) -> impl Iterator<Item = Range<T>> + '_ {
+        return self
+            .ranges
+            .windows(2)
+            .map(|pair| Range::new(pair[0].stop + one(), pair[1].start - one()));
+    }
+
+    /// The ranges of integers within the bounds that are not covered,
+    /// including any before the first range and after the last one
+    pub fn gaps_within(&self, bounds: Range<T>) -> Vec<Range<T>> {
+        return IntervalSet::from_ranges([bounds]).difference(self).ranges.clone();
+    }
+}